@@ -1,7 +1,8 @@
+use std::borrow::Borrow;
 use std::collections::{btree_map, BTreeMap};
 use std::mem;
 
-use crate::{Entry, GenericMap, OccupiedEntry, VacantEntry};
+use crate::{Entry, GenericMap, Lookup, OccupiedEntry, VacantEntry};
 
 impl<K: Ord, V> GenericMap for BTreeMap<K, V> {
     type K = K;
@@ -36,26 +37,10 @@ impl<K: Ord, V> GenericMap for BTreeMap<K, V> {
         self.is_empty()
     }
 
-    fn contains_key(&self, key: &K) -> bool {
-        self.contains_key(key)
-    }
-
-    fn get(&self, key: &K) -> Option<&V> {
-        self.get(key)
-    }
-
-    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        self.get_mut(key)
-    }
-
     fn insert(&mut self, key: K, value: V) -> Option<V> {
         self.insert(key, value)
     }
 
-    fn remove(&mut self, key: &K) -> Option<V> {
-        self.remove(key)
-    }
-
     fn drain(&mut self) -> Self::DrainIter<'_> {
         mem::take(self).into_iter()
     }
@@ -76,7 +61,38 @@ impl<K: Ord, V> GenericMap for BTreeMap<K, V> {
     }
 }
 
+impl<K: Ord + Borrow<Q>, Q: ?Sized + Ord, V> Lookup<Q> for BTreeMap<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
+    }
+}
+
 impl<'a, K: Ord, V> VacantEntry<'a, K, V> for btree_map::VacantEntry<'a, K, V> {
+    type Occupied = btree_map::OccupiedEntry<'a, K, V>;
+
     fn key(&self) -> &K {
         self.key()
     }
@@ -84,6 +100,10 @@ impl<'a, K: Ord, V> VacantEntry<'a, K, V> for btree_map::VacantEntry<'a, K, V> {
     fn insert(self, value: V) -> &'a mut V {
         self.insert(value)
     }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
 }
 
 impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> for btree_map::OccupiedEntry<'a, K, V> {
@@ -99,6 +119,10 @@ impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> for btree_map::OccupiedEntry<'a, K,
         self.remove()
     }
 
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
     fn get(&self) -> &V {
         self.get()
     }
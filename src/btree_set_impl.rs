@@ -0,0 +1,52 @@
+use std::borrow::Borrow;
+use std::collections::{btree_set, BTreeSet};
+use std::mem;
+
+use crate::{GenericSet, SetLookup};
+
+impl<T: Ord> GenericSet for BTreeSet<T> {
+    type T = T;
+    type Iter<'a> = btree_set::Iter<'a, T>
+    where
+        T: 'a;
+
+    type DrainIter<'a> = btree_set::IntoIter<T>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn insert(&mut self, value: T) -> bool {
+        self.insert(value)
+    }
+
+    fn drain(&mut self) -> Self::DrainIter<'_> {
+        mem::take(self).into_iter()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+impl<T: Ord + Borrow<Q>, Q: ?Sized + Ord> SetLookup<Q> for BTreeSet<T> {
+    type Value = T;
+
+    fn contains(&self, value: &Q) -> bool {
+        self.contains(value)
+    }
+
+    fn remove(&mut self, value: &Q) -> bool {
+        self.remove(value)
+    }
+
+    fn take(&mut self, value: &Q) -> Option<T> {
+        self.take(value)
+    }
+}
@@ -0,0 +1,192 @@
+use std::borrow::Borrow;
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+use crate::{Entry, GenericMap, Lookup, OccupiedEntry, VacantEntry};
+
+pub trait EntryRef<Q: ?Sized>: GenericMap {
+    type VacEntryRef<'a>: VacantEntry<'a, Self::K, Self::V, Occupied = Self::OccupEntry<'a>>
+    where
+        Self: 'a,
+        Q: 'a;
+    type OccupEntryRef<'a>: OccupiedEntry<'a, Self::K, Self::V>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>>;
+}
+
+pub trait KeySource<K> {
+    fn key(&self) -> &K;
+    fn into_key(self) -> K;
+}
+
+impl<K> KeySource<K> for K {
+    fn key(&self) -> &K {
+        self
+    }
+
+    fn into_key(self) -> K {
+        self
+    }
+}
+
+pub struct PendingKey<'a, K, Q: ?Sized> {
+    borrowed: &'a Q,
+    owned: OnceCell<K>,
+}
+
+impl<'a, K, Q: ?Sized> PendingKey<'a, K, Q> {
+    pub(crate) fn new(borrowed: &'a Q) -> Self {
+        Self {
+            borrowed,
+            owned: OnceCell::new(),
+        }
+    }
+
+    pub fn borrowed(&self) -> &'a Q {
+        self.borrowed
+    }
+}
+
+impl<K, Q: ?Sized + ToOwned<Owned = K>> KeySource<K> for PendingKey<'_, K, Q> {
+    fn key(&self) -> &K {
+        self.owned.get_or_init(|| self.borrowed.to_owned())
+    }
+
+    fn into_key(self) -> K {
+        self.owned
+            .into_inner()
+            .unwrap_or_else(|| self.borrowed.to_owned())
+    }
+}
+
+pub struct VacantRef<'a, M: GenericMap, Q: ?Sized> {
+    map: &'a mut M,
+    key: PendingKey<'a, M::K, Q>,
+}
+
+impl<'a, M: GenericMap, Q: ?Sized> VacantRef<'a, M, Q> {
+    pub(crate) fn new(map: &'a mut M, key: &'a Q) -> Self {
+        Self {
+            map,
+            key: PendingKey::new(key),
+        }
+    }
+
+    pub fn borrowed_key(&self) -> &'a Q {
+        self.key.borrowed()
+    }
+
+    pub fn key(&self) -> &M::K
+    where
+        Q: ToOwned<Owned = M::K>,
+    {
+        self.key.key()
+    }
+
+    pub fn insert(self, value: M::V) -> &'a mut M::V
+    where
+        Q: ToOwned<Owned = M::K>,
+    {
+        self.insert_entry(value).into_mut()
+    }
+
+    pub fn insert_entry(self, value: M::V) -> M::OccupEntry<'a>
+    where
+        Q: ToOwned<Owned = M::K>,
+    {
+        reinsert(self.map, self.key.into_key(), value)
+    }
+}
+
+impl<'a, M: GenericMap, Q: ?Sized + ToOwned<Owned = M::K>> VacantEntry<'a, M::K, M::V>
+    for VacantRef<'a, M, Q>
+{
+    type Occupied = M::OccupEntry<'a>;
+
+    fn key(&self) -> &M::K {
+        self.key()
+    }
+
+    fn insert(self, value: M::V) -> &'a mut M::V {
+        self.insert(value)
+    }
+
+    fn insert_entry(self, value: M::V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
+}
+
+fn reinsert<M: GenericMap>(map: &mut M, key: M::K, value: M::V) -> M::OccupEntry<'_> {
+    match map.entry(key) {
+        Entry::Vacant(entry) => entry.insert_entry(value),
+        Entry::Occupied(mut entry) => {
+            entry.insert(value);
+            entry
+        }
+    }
+}
+
+fn resolve<'a, M, Q>(map: &'a mut M, key: &'a Q) -> Entry<VacantRef<'a, M, Q>, M::OccupEntry<'a>>
+where
+    M: GenericMap + Lookup<Q, Key = M::K, Value = M::V>,
+    Q: ?Sized,
+{
+    match map.remove_entry(key) {
+        Some((key, value)) => Entry::Occupied(reinsert(map, key, value)),
+        None => Entry::Vacant(VacantRef::new(map, key)),
+    }
+}
+
+impl<K, Q, V, S> EntryRef<Q> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: ?Sized + Eq + Hash + ToOwned<Owned = K>,
+    S: BuildHasher + Default,
+{
+    type VacEntryRef<'a> = VacantRef<'a, Self, Q>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    type OccupEntryRef<'a> = Self::OccupEntry<'a>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        resolve(self, key)
+    }
+}
+
+impl<K, Q, V> EntryRef<Q> for BTreeMap<K, V>
+where
+    K: Ord + Borrow<Q>,
+    Q: ?Sized + Ord + ToOwned<Owned = K>,
+{
+    type VacEntryRef<'a> = VacantRef<'a, Self, Q>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    type OccupEntryRef<'a> = Self::OccupEntry<'a>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        resolve(self, key)
+    }
+}
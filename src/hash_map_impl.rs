@@ -1,7 +1,8 @@
+use std::borrow::Borrow;
 use std::collections::{hash_map, HashMap};
 use std::hash::{BuildHasher, Hash};
 
-use crate::{Entry, GenericMap, OccupiedEntry, VacantEntry};
+use crate::{Entry, GenericMap, Lookup, OccupiedEntry, VacantEntry};
 
 impl<K: Eq + Hash, V, S: BuildHasher + Default> GenericMap for HashMap<K, V, S> {
     type K = K;
@@ -38,26 +39,10 @@ impl<K: Eq + Hash, V, S: BuildHasher + Default> GenericMap for HashMap<K, V, S>
         self.is_empty()
     }
 
-    fn contains_key(&self, key: &K) -> bool {
-        self.contains_key(key)
-    }
-
-    fn get(&self, key: &K) -> Option<&V> {
-        self.get(key)
-    }
-
-    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        self.get_mut(key)
-    }
-
     fn insert(&mut self, key: K, value: V) -> Option<V> {
         self.insert(key, value)
     }
 
-    fn remove(&mut self, key: &K) -> Option<V> {
-        self.remove(key)
-    }
-
     fn drain(&mut self) -> Self::DrainIter<'_> {
         self.drain()
     }
@@ -78,7 +63,40 @@ impl<K: Eq + Hash, V, S: BuildHasher + Default> GenericMap for HashMap<K, V, S>
     }
 }
 
+impl<K: Eq + Hash + Borrow<Q>, Q: ?Sized + Eq + Hash, V, S: BuildHasher> Lookup<Q>
+    for HashMap<K, V, S>
+{
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
+    }
+}
+
 impl<'a, K, V> VacantEntry<'a, K, V> for hash_map::VacantEntry<'a, K, V> {
+    type Occupied = hash_map::OccupiedEntry<'a, K, V>;
+
     fn key(&self) -> &K {
         self.key()
     }
@@ -86,6 +104,10 @@ impl<'a, K, V> VacantEntry<'a, K, V> for hash_map::VacantEntry<'a, K, V> {
     fn insert(self, value: V) -> &'a mut V {
         self.insert(value)
     }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
 }
 
 impl<'a, K, V> OccupiedEntry<'a, K, V> for hash_map::OccupiedEntry<'a, K, V> {
@@ -101,6 +123,10 @@ impl<'a, K, V> OccupiedEntry<'a, K, V> for hash_map::OccupiedEntry<'a, K, V> {
         self.remove()
     }
 
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
     fn get(&self) -> &V {
         self.get()
     }
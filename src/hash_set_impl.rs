@@ -0,0 +1,55 @@
+use std::borrow::Borrow;
+use std::collections::{hash_set, HashSet};
+use std::hash::{BuildHasher, Hash};
+
+use crate::{GenericSet, SetLookup};
+
+impl<T: Eq + Hash, S: BuildHasher + Default> GenericSet for HashSet<T, S> {
+    type T = T;
+    type Iter<'a> = hash_set::Iter<'a, T>
+    where
+        T: 'a,
+        S: 'a;
+
+    type DrainIter<'a> = hash_set::Drain<'a, T>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn insert(&mut self, value: T) -> bool {
+        self.insert(value)
+    }
+
+    fn drain(&mut self) -> Self::DrainIter<'_> {
+        self.drain()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+impl<T: Eq + Hash + Borrow<Q>, Q: ?Sized + Eq + Hash, S: BuildHasher> SetLookup<Q>
+    for HashSet<T, S>
+{
+    type Value = T;
+
+    fn contains(&self, value: &Q) -> bool {
+        self.contains(value)
+    }
+
+    fn remove(&mut self, value: &Q) -> bool {
+        self.remove(value)
+    }
+
+    fn take(&mut self, value: &Q) -> Option<T> {
+        self.take(value)
+    }
+}
@@ -1,160 +1,607 @@
-use std::collections::{hash_map, HashMap};
-use std::hash::Hash;
-use std::{iter, mem};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::{iter, mem, slice, vec};
+
+use hashbrown::hash_table::{self, HashTable};
 
 use crate::clear::Clear;
 use crate::drain::Drain;
-use crate::GenericMap;
+use crate::entry_ref::{EntryRef, PendingKey};
+use crate::{GenericMap, Lookup};
 
+use self::backend::{Binary, DoubleEndedBackend, Heap, HeapBackend, MinMax};
 use self::comparator::{Comparator, Max, Min};
-use self::indexed_heap::{Index, IndexedHeap};
+use self::indexed_heap::Index;
 
-pub use self::entry::{Entry, OccupEntry, VacEntry};
+pub use self::entry::{Entry, OccupEntry, VacEntry, VacEntryRef};
+pub use self::indexed_priority_queue::IndexedPriorityQueue;
+pub use self::peek_mut::PeekMut;
+pub use self::radix_heap::HashedRadixHeap;
+pub use self::stable::StableHashedHeap;
+pub use self::value_heap::{HashedValueHeap, HashedValueMaxHeap, HashedValueMinHeap};
 
 mod entry;
 mod indexed_heap;
+mod min_max_heap;
+mod pairing_heap;
+mod peek_mut;
 
+pub mod backend;
 pub mod comparator;
+pub mod indexed_priority_queue;
+pub mod radix_heap;
+pub mod stable;
+pub mod value_heap;
+
+pub struct HashedHeap<K, V, C, B: HeapBackend = Binary, S = RandomState> {
+    table: HashTable<usize>,
+    slots: Vec<Slot<V>>,
+    heap: KeyHeap<K, C, B>,
+    hasher: S,
+}
 
-pub struct HashedHeap<K, V, C> {
-    map: HashMap<K, (V, Index)>,
-    heap: IndexedHeap<K, C>,
+struct Slot<V> {
+    value: V,
+    hash: u64,
+    index: Index,
 }
 
-pub type HashedMaxHeap<K, V> = HashedHeap<K, V, Max<K>>;
-pub type HashedMinHeap<K, V> = HashedHeap<K, V, Min<K>>;
+struct KeyOrder<C>(C);
+
+type KeyHeap<K, C, B> = <B as HeapBackend>::Heap<(K, usize), KeyOrder<C>>;
+
+impl<K, C: Comparator<K>> Comparator<(K, usize)> for KeyOrder<C> {
+    fn favors(&self, a: &(K, usize), b: &(K, usize)) -> bool {
+        self.0.favors(&a.0, &b.0)
+    }
+}
 
-impl<K, V> HashedMaxHeap<K, V> {
+fn reindex<'a, K: 'a, V>(
+    slots: &mut [Slot<V>],
+    changes: impl Iterator<Item = (Index, &'a (K, usize))>,
+) {
+    for (new_index, &(_, slot)) in changes {
+        slots[slot].index = new_index;
+    }
+}
+
+fn arrange_by_slot<K>(keys: &mut [(K, usize)]) {
+    for i in 0..keys.len() {
+        while keys[i].1 != i {
+            let j = keys[i].1;
+            keys.swap(i, j);
+        }
+    }
+}
+
+pub type HashedMaxHeap<K, V, S = RandomState> = HashedHeap<K, V, Max<K>, Binary, S>;
+pub type HashedMinHeap<K, V, S = RandomState> = HashedHeap<K, V, Min<K>, Binary, S>;
+pub type HashedMinMaxHeap<K, V, S = RandomState> = HashedHeap<K, V, Min<K>, MinMax, S>;
+
+impl<K, V, S> HashedMaxHeap<K, V, S> {
     pub fn max_key(&self) -> Option<&K> {
         self.top_key()
     }
+
+    pub fn pop_max(&mut self) -> Option<(K, V)>
+    where
+        K: PartialOrd,
+    {
+        self.pop_top()
+    }
 }
 
-impl<K, V> HashedMinHeap<K, V> {
+impl<K, V, S> HashedMinHeap<K, V, S> {
     pub fn min_key(&self) -> Option<&K> {
         self.top_key()
     }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)>
+    where
+        K: PartialOrd,
+    {
+        self.pop_top()
+    }
 }
 
-impl<K, V, C> HashedHeap<K, V, C> {
+impl<K, V, S> HashedMinMaxHeap<K, V, S> {
+    pub fn min_key(&self) -> Option<&K> {
+        self.top_key()
+    }
+
+    pub fn max_key(&self) -> Option<&K>
+    where
+        K: PartialOrd,
+    {
+        self.bottom_key()
+    }
+
+    pub fn peek_min(&self) -> Option<(&K, &V)> {
+        self.peek()
+    }
+
+    pub fn peek_max(&self) -> Option<(&K, &V)>
+    where
+        K: PartialOrd,
+    {
+        self.peek_bottom()
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)>
+    where
+        K: PartialOrd,
+    {
+        self.pop_top()
+    }
+
+    pub fn pop_max(&mut self) -> Option<(K, V)>
+    where
+        K: PartialOrd,
+    {
+        self.pop_bottom()
+    }
+}
+
+impl<K, V, C, B: HeapBackend> HashedHeap<K, V, C, B> {
     pub fn new() -> Self
     where
         C: Default,
     {
+        Self::with_comparator(C::default())
+    }
+
+    pub fn from_vec(entries: Vec<(K, V)>) -> Self
+    where
+        K: Eq + Hash,
+        C: Comparator<K> + Default,
+    {
+        let mut result = Self::new();
+        result.extend(entries);
+        result
+    }
+
+    pub fn with_comparator(compare: C) -> Self {
+        Self::with_comparator_and_hasher(compare, RandomState::new())
+    }
+}
+
+impl<K, V, C, B: HeapBackend, S> HashedHeap<K, V, C, B, S> {
+    pub fn with_hasher(hasher: S) -> Self
+    where
+        C: Default,
+    {
+        Self::with_comparator_and_hasher(C::default(), hasher)
+    }
+
+    pub fn with_comparator_and_hasher(compare: C, hasher: S) -> Self {
         Self {
-            map: HashMap::new(),
-            heap: IndexedHeap::new(),
+            table: HashTable::new(),
+            slots: Vec::new(),
+            heap: Heap::with_comparator(KeyOrder(compare)),
+            hasher,
         }
     }
 
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.heap.comparator().0
+    }
+
     pub fn len(&self) -> usize {
-        self.map.len()
+        self.slots.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.slots.is_empty()
     }
 
-    pub fn contains_key(&self, key: &K) -> bool
+    pub fn contains_key<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
     where
-        K: Eq + Hash,
+        K: Borrow<Q>,
+        S: BuildHasher,
     {
-        self.map.contains_key(key)
+        self.find(key).is_some()
     }
 
-    pub fn get(&self, key: &K) -> Option<&V>
+    pub fn get<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<&V>
     where
-        K: Eq + Hash,
+        K: Borrow<Q>,
+        S: BuildHasher,
     {
-        self.map.get(key).map(|(v, _)| v)
+        let slot = self.find(key)?;
+        Some(&self.slots[slot].value)
     }
 
-    pub fn get_mut(&mut self, key: &K) -> Option<&mut V>
+    pub fn get_key_value<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<(&K, &V)>
     where
-        K: Eq + Hash,
+        K: Borrow<Q>,
+        S: BuildHasher,
+    {
+        let slot = self.find(key)?;
+        Some((self.key_at(slot), &self.slots[slot].value))
+    }
+
+    pub fn get_mut<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        S: BuildHasher,
     {
-        self.map.get_mut(key).map(|(v, _)| v)
+        let slot = self.find(key)?;
+        Some(&mut self.slots[slot].value)
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V>
     where
-        K: Eq + Hash + Clone,
+        K: Eq + Hash,
+        S: BuildHasher,
         C: Comparator<K>,
     {
-        match self.map.entry(key) {
-            hash_map::Entry::Occupied(occ) => Some(mem::replace(&mut occ.into_mut().0, value)),
-            hash_map::Entry::Vacant(vac) => {
-                let (index, changes) = self.heap.insert(vac.key().clone());
-                vac.insert((value, index));
-                for (new_index, k) in changes {
-                    self.map.get_mut(k).unwrap().1 = new_index;
-                }
+        let hash = self.hasher.hash_one(&key);
+        let (slots, heap) = (&self.slots, &self.heap);
+        match self.table.entry(
+            hash,
+            |&slot| heap[slots[slot].index].0 == key,
+            |&slot| slots[slot].hash,
+        ) {
+            hash_table::Entry::Occupied(occ) => {
+                Some(mem::replace(&mut self.slots[*occ.get()].value, value))
+            }
+            hash_table::Entry::Vacant(vac) => {
+                vac.insert(self.slots.len());
+                self.push_slot(key, value, hash);
                 None
             }
         }
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V>
+    pub fn remove<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<V>
     where
-        K: Eq + Hash,
+        K: Borrow<Q>,
+        S: BuildHasher,
         C: Comparator<K>,
     {
-        let (result, index) = self.map.remove(key)?;
-        let (k, changes) = self.heap.remove(index);
-        assert!(k == *key);
-        for (new_index, k) in changes {
-            self.map.get_mut(&k).unwrap().1 = new_index;
-        }
-        Some(result)
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        S: BuildHasher,
+        C: Comparator<K>,
+    {
+        let hash = self.hasher.hash_one(key);
+        let (slots, heap) = (&self.slots, &self.heap);
+        let (slot, _) = self
+            .table
+            .find_entry(hash, |&slot| heap[slots[slot].index].0.borrow() == key)
+            .ok()?
+            .remove();
+        Some(self.remove_slot(slot))
     }
 
     pub fn drain(&mut self) -> DrainIter<'_, K, V> {
-        self.heap.clear();
-        self.map.drain().map(|(k, (v, _))| (k, v))
+        self.table.clear();
+        arrange_by_slot(self.heap.as_mut_slice());
+        DrainIter(self.heap.drain().zip(self.slots.drain(..)))
     }
 
-    pub fn entry(&mut self, key: K) -> crate::Entry<VacEntry<'_, K, V, C>, OccupEntry<'_, K, V, C>>
+    #[allow(clippy::type_complexity)]
+    pub fn entry(
+        &mut self,
+        key: K,
+    ) -> crate::Entry<VacEntry<'_, K, V, C, B, S>, OccupEntry<'_, K, V, C, B, S>>
     where
         K: Eq + Hash,
+        S: BuildHasher,
     {
-        let map = &mut self.map as *mut HashMap<K, (V, Index)>;
-        match self.map.entry(key) {
-            hash_map::Entry::Occupied(occ) => {
-                crate::Entry::Occupied(unsafe { Entry::new(map, &mut self.heap, occ) })
-            }
-            hash_map::Entry::Vacant(vac) => {
-                crate::Entry::Vacant(unsafe { Entry::new(map, &mut self.heap, vac) })
-            }
+        let hash = self.hasher.hash_one(&key);
+        match self.find_hashed(hash, &key) {
+            Some(slot) => crate::Entry::Occupied(Entry::occupied(self, slot)),
+            None => crate::Entry::Vacant(Entry::vacant(self, key, hash)),
         }
     }
 
     pub fn iter(&self) -> Iter<'_, K, V> {
-        self.map.iter().map(|(k, (v, _))| (k, v))
+        Iter {
+            slots: self.slots.iter(),
+            keys: self.heap.as_slice(),
+        }
     }
 
     pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
-        self.map.iter_mut().map(|(k, (v, _))| (k, v))
+        IterMut {
+            slots: self.slots.iter_mut(),
+            keys: self.heap.as_slice(),
+        }
     }
 
     pub fn top_key(&self) -> Option<&K> {
-        self.heap.peek()
+        self.heap.peek().map(|(k, _)| k)
+    }
+
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        let (key, slot) = self.heap.peek()?;
+        Some((key, &self.slots[*slot].value))
+    }
+
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, K, V, C, B, S>> {
+        let &(_, slot) = self.heap.peek()?;
+        Some(PeekMut::new(self, slot))
+    }
+
+    pub fn pop_top(&mut self) -> Option<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        let &(_, slot) = self.heap.peek()?;
+        self.unlink(slot);
+        Some(self.remove_slot(slot))
+    }
+
+    pub fn iter_sorted(&self) -> SortedIter<'_, K, V, C, B, S>
+    where
+        C: Comparator<K>,
+    {
+        SortedIter {
+            heap: self,
+            sorted: self.heap.sorted(),
+        }
+    }
+
+    pub fn top_k(&self, k: usize) -> Vec<(&K, &V)>
+    where
+        C: Comparator<K>,
+    {
+        self.iter_sorted().take(k).collect()
+    }
+
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, K, V, C, B, S>
+    where
+        C: Comparator<K>,
+    {
+        DrainSorted { heap: self }
+    }
+
+    pub fn into_sorted_vec(mut self) -> Vec<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        self.drain_sorted().collect()
+    }
+
+    pub fn append(&mut self, other: &mut Self)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+        C: Comparator<K>,
+    {
+        self.merge_with(other, |_, value, other| *value = other);
+    }
+
+    pub fn merge_with<F>(&mut self, other: &mut Self, merge: F)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+        C: Comparator<K>,
+        F: FnMut(&K, &mut V, V),
+    {
+        self.extend_with(other.drain(), merge);
+    }
+
+    pub fn extend_with<I, F>(&mut self, iter: I, mut merge: F)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+        C: Comparator<K>,
+        I: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, &mut V, V),
+    {
+        let iter = iter.into_iter();
+        let start = self.len();
+        let additional = iter.size_hint().0;
+        let slots = &self.slots;
+        self.table.reserve(additional, |&slot| slots[slot].hash);
+        self.slots.reserve(additional);
+        self.heap.reserve(additional);
+        for (key, value) in iter {
+            let hash = self.hasher.hash_one(&key);
+            let (slots, heap) = (&self.slots, &self.heap);
+            match self.table.entry(
+                hash,
+                |&slot| heap[slots[slot].index].0 == key,
+                |&slot| slots[slot].hash,
+            ) {
+                hash_table::Entry::Occupied(occ) => {
+                    let slot = &mut self.slots[*occ.get()];
+                    merge(&self.heap[slot.index].0, &mut slot.value, value);
+                }
+                hash_table::Entry::Vacant(vac) => {
+                    vac.insert(self.slots.len());
+                    self.push_slot_unordered(key, value, hash);
+                }
+            }
+        }
+        reindex(&mut self.slots, self.heap.restore_from(start));
+    }
+
+    pub fn pop_while<F>(&mut self, pred: F) -> PopWhile<'_, K, V, C, F, B, S>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        PopWhile { heap: self, pred }
+    }
+
+    fn key_at(&self, slot: usize) -> &K {
+        &self.heap[self.slots[slot].index].0
+    }
+
+    fn find<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        S: BuildHasher,
+    {
+        self.find_hashed(self.hasher.hash_one(key), key)
+    }
+
+    fn find_hashed<Q: ?Sized + Eq>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        self.find_with(hash, |k| k.borrow() == key)
+    }
+
+    fn find_with(&self, hash: u64, eq: impl Fn(&K) -> bool) -> Option<usize> {
+        self.table
+            .find(hash, |&slot| eq(self.key_at(slot)))
+            .copied()
+    }
+
+    fn push_slot(&mut self, key: K, value: V, hash: u64)
+    where
+        C: Comparator<K>,
+    {
+        let slot = self.slots.len();
+        self.slots.push(Slot {
+            value,
+            hash,
+            index: Index::TOP,
+        });
+        let (index, changes) = self.heap.insert((key, slot));
+        reindex(&mut self.slots, changes);
+        self.slots[slot].index = index;
+    }
+
+    fn push_slot_unordered(&mut self, key: K, value: V, hash: u64) {
+        let index = self.heap.push_unordered((key, self.slots.len()));
+        self.slots.push(Slot { value, hash, index });
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        self.table
+            .find_entry(self.slots[slot].hash, |&s| s == slot)
+            .unwrap()
+            .remove();
+    }
+
+    fn remove_slot(&mut self, slot: usize) -> (K, V)
+    where
+        C: Comparator<K>,
+    {
+        let ((key, _), changes) = self.heap.remove(self.slots[slot].index);
+        reindex(&mut self.slots, changes);
+        let Slot { value, .. } = self.slots.swap_remove(slot);
+        if let Some(moved) = self.slots.get(slot) {
+            let last = self.slots.len();
+            self.heap[moved.index].1 = slot;
+            *self.table.find_mut(moved.hash, |&s| s == last).unwrap() = slot;
+        }
+        (key, value)
     }
 }
 
-impl<K, V, C: Default> Default for HashedHeap<K, V, C> {
+impl<K, V, C, B: DoubleEndedBackend, S> HashedHeap<K, V, C, B, S> {
+    pub fn bottom_key(&self) -> Option<&K>
+    where
+        C: Comparator<K>,
+    {
+        let index = B::bottom(&self.heap)?;
+        Some(&self.heap[index].0)
+    }
+
+    pub fn peek_bottom(&self) -> Option<(&K, &V)>
+    where
+        C: Comparator<K>,
+    {
+        let (key, slot) = &self.heap[B::bottom(&self.heap)?];
+        Some((key, &self.slots[*slot].value))
+    }
+
+    pub fn pop_bottom(&mut self) -> Option<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        let slot = self.heap[B::bottom(&self.heap)?].1;
+        self.unlink(slot);
+        Some(self.remove_slot(slot))
+    }
+}
+
+pub struct PopWhile<'a, K, V, C, F, B: HeapBackend = Binary, S = RandomState> {
+    heap: &'a mut HashedHeap<K, V, C, B, S>,
+    pred: F,
+}
+
+impl<'a, K, V, C: Comparator<K>, F: FnMut(&K, &V) -> bool, B: HeapBackend, S> Iterator
+    for PopWhile<'a, K, V, C, F, B, S>
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.heap.peek()?;
+        if (self.pred)(k, v) {
+            self.heap.pop_top()
+        } else {
+            None
+        }
+    }
+}
+
+pub struct SortedIter<'a, K, V, C, B: HeapBackend = Binary, S = RandomState> {
+    heap: &'a HashedHeap<K, V, C, B, S>,
+    sorted: <KeyHeap<K, C, B> as Heap<(K, usize), KeyOrder<C>>>::Sorted,
+}
+
+impl<'a, K, V, C: Comparator<K>, B: HeapBackend, S> Iterator for SortedIter<'a, K, V, C, B, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let heap = self.heap;
+        let (key, slot) = &heap.heap[heap.heap.next_sorted(&mut self.sorted)?];
+        Some((key, &heap.slots[*slot].value))
+    }
+}
+
+pub struct DrainSorted<'a, K, V, C, B: HeapBackend = Binary, S = RandomState> {
+    heap: &'a mut HashedHeap<K, V, C, B, S>,
+}
+
+impl<'a, K, V, C: Comparator<K>, B: HeapBackend, S> Iterator for DrainSorted<'a, K, V, C, B, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.heap.pop_top()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+impl<'a, K, V, C, B: HeapBackend, S> Drop for DrainSorted<'a, K, V, C, B, S> {
+    fn drop(&mut self) {
+        self.heap.clear();
+    }
+}
+
+impl<K, V, C: Default, B: HeapBackend, S: Default> Default for HashedHeap<K, V, C, B, S> {
     fn default() -> Self {
-        Self::new()
+        Self::with_hasher(S::default())
     }
 }
 
-impl<K, V, C> Clear for HashedHeap<K, V, C> {
+impl<K, V, C, B: HeapBackend, S> Clear for HashedHeap<K, V, C, B, S> {
     fn clear(&mut self) {
-        self.map.clear();
+        self.table.clear();
+        self.slots.clear();
         self.heap.clear();
     }
 }
 
-impl<K, V, C> Drain for HashedHeap<K, V, C> {
+impl<K, V, C, B: HeapBackend, S> Drain for HashedHeap<K, V, C, B, S> {
     type Output<'a> = DrainIter<'a, K, V>
     where
         Self: 'a;
@@ -164,35 +611,102 @@ impl<K, V, C> Drain for HashedHeap<K, V, C> {
     }
 }
 
-impl<K, V, C> IntoIterator for HashedHeap<K, V, C> {
+impl<K, V, C, B: HeapBackend, S> IntoIterator for HashedHeap<K, V, C, B, S> {
     type Item = (K, V);
-    type IntoIter = iter::Map<hash_map::IntoIter<K, (V, Index)>, fn((K, (V, Index))) -> (K, V)>;
+    type IntoIter = IntoIter<K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.map.into_iter().map(|(k, (v, _))| (k, v))
+        let mut keys = self.heap.into_vec();
+        arrange_by_slot(&mut keys);
+        IntoIter(keys.into_iter().zip(self.slots))
     }
 }
 
-impl<K: Eq + Hash + Clone, V, C: Comparator<K>> Extend<(K, V)> for HashedHeap<K, V, C> {
+impl<K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend, S: BuildHasher> Extend<(K, V)>
+    for HashedHeap<K, V, C, B, S>
+{
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
-        for (k, v) in iter {
-            self.insert(k, v);
-        }
+        self.extend_with(iter, |_, value, other| *value = other);
+    }
+}
+
+impl<K: Eq + Hash, V, C: Comparator<K> + Default, B: HeapBackend, S: BuildHasher + Default>
+    FromIterator<(K, V)> for HashedHeap<K, V, C, B, S>
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut result = Self::default();
+        result.extend(iter);
+        result
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    slots: slice::Iter<'a, Slot<V>>,
+    keys: &'a [(K, usize)],
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.next()?;
+        Some((&self.keys[slot.index.0].0, &slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+pub struct IterMut<'a, K, V> {
+    slots: slice::IterMut<'a, Slot<V>>,
+    keys: &'a [(K, usize)],
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.next()?;
+        Some((&self.keys[slot.index.0].0, &mut slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+pub struct DrainIter<'a, K, V>(iter::Zip<vec::Drain<'a, (K, usize)>, vec::Drain<'a, Slot<V>>>);
+
+impl<'a, K, V> Iterator for DrainIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|((k, _), slot)| (k, slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
     }
 }
 
-pub type Iter<'a, K, V> =
-    iter::Map<hash_map::Iter<'a, K, (V, Index)>, fn((&'a K, &'a (V, Index))) -> (&'a K, &'a V)>;
+pub struct IntoIter<K, V>(iter::Zip<vec::IntoIter<(K, usize)>, vec::IntoIter<Slot<V>>>);
 
-pub type IterMut<'a, K, V> = iter::Map<
-    hash_map::IterMut<'a, K, (V, Index)>,
-    fn((&'a K, &'a mut (V, Index))) -> (&'a K, &'a mut V),
->;
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
 
-pub type DrainIter<'a, K, V> =
-    iter::Map<hash_map::Drain<'a, K, (V, Index)>, fn((K, (V, Index))) -> (K, V)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|((k, _), slot)| (k, slot.value))
+    }
 
-impl<K: Eq + Hash + Clone, V, C: Comparator<K>> GenericMap for HashedHeap<K, V, C> {
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend, S: BuildHasher> GenericMap
+    for HashedHeap<K, V, C, B, S>
+{
     type K = K;
     type V = V;
     type Iter<'a> = Iter<'a, K, V>
@@ -211,11 +725,11 @@ impl<K: Eq + Hash + Clone, V, C: Comparator<K>> GenericMap for HashedHeap<K, V,
     where
         Self: 'a;
 
-    type VacEntry<'a> = VacEntry<'a, K, V, C>
+    type VacEntry<'a> = VacEntry<'a, K, V, C, B, S>
     where
         Self: 'a;
 
-    type OccupEntry<'a> = OccupEntry<'a, K, V, C>
+    type OccupEntry<'a> = OccupEntry<'a, K, V, C, B, S>
     where
         Self: 'a;
 
@@ -227,39 +741,274 @@ impl<K: Eq + Hash + Clone, V, C: Comparator<K>> GenericMap for HashedHeap<K, V,
         self.is_empty()
     }
 
-    fn contains_key(&self, key: &K) -> bool {
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn drain(&mut self) -> Self::DrainIter<'_> {
+        self.drain()
+    }
+
+    fn entry(&mut self, key: K) -> crate::Entry<Self::VacEntry<'_>, Self::OccupEntry<'_>> {
+        self.entry(key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        self.iter_mut()
+    }
+}
+
+impl<K, Q, V, C, B, S> Lookup<Q> for HashedHeap<K, V, C, B, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: ?Sized + Eq + Hash,
+    C: Comparator<K>,
+    B: HeapBackend,
+    S: BuildHasher,
+{
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &Q) -> bool {
         self.contains_key(key)
     }
 
-    fn get(&self, key: &K) -> Option<&V> {
+    fn get(&self, key: &Q) -> Option<&V> {
         self.get(key)
     }
 
-    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        self.get_mut(key)
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
     }
 
-    fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.insert(key, value)
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
     }
 
-    fn remove(&mut self, key: &K) -> Option<V> {
+    fn remove(&mut self, key: &Q) -> Option<V> {
         self.remove(key)
     }
 
-    fn drain(&mut self) -> Self::DrainIter<'_> {
-        self.drain()
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
     }
+}
 
-    fn entry(&mut self, key: K) -> crate::Entry<Self::VacEntry<'_>, Self::OccupEntry<'_>> {
-        self.entry(key)
+impl<K, Q, V, C, B, S> EntryRef<Q> for HashedHeap<K, V, C, B, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: ?Sized + Eq + Hash + ToOwned<Owned = K>,
+    C: Comparator<K>,
+    B: HeapBackend,
+    S: BuildHasher,
+{
+    type VacEntryRef<'a> = VacEntryRef<'a, K, V, C, Q, B, S>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    type OccupEntryRef<'a> = OccupEntry<'a, K, V, C, B, S>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> crate::Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        let hash = self.hasher.hash_one(key);
+        match self.find_hashed(hash, key) {
+            Some(slot) => crate::Entry::Occupied(Entry::occupied(self, slot)),
+            None => crate::Entry::Vacant(Entry::vacant(self, PendingKey::new(key), hash)),
+        }
     }
+}
 
-    fn iter(&self) -> Self::Iter<'_> {
-        self.iter()
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::VacantEntry;
+
+    #[test]
+    fn pop_min_yields_ascending_keys() {
+        let mut heap = HashedMinHeap::new();
+        for key in [5, 1, 4, 2, 3] {
+            heap.insert(key, key * 10);
+        }
+        assert_eq!(heap.peek(), Some((&1, &10)));
+        let popped: Vec<_> = iter::from_fn(|| heap.pop_min()).collect();
+        assert_eq!(popped, [(1, 10), (2, 20), (3, 30), (4, 40), (5, 50)]);
+        assert!(heap.is_empty());
+        assert_eq!(heap.peek(), None);
     }
 
-    fn iter_mut(&mut self) -> Self::IterMut<'_> {
-        self.iter_mut()
+    #[test]
+    fn pop_max_after_removing_the_top() {
+        let mut heap = HashedMaxHeap::new();
+        for key in [3, 9, 7, 1] {
+            heap.insert(key, ());
+        }
+        assert_eq!(heap.remove(&9), Some(()));
+        assert_eq!(heap.pop_max(), Some((7, ())));
+        assert_eq!(heap.max_key(), Some(&3));
+    }
+
+    #[test]
+    fn pop_while_stops_at_the_first_rejected_entry() {
+        let mut heap = HashedMinHeap::new();
+        for key in [4, 2, 8, 6] {
+            heap.insert(key, key % 3 == 0);
+        }
+        let popped: Vec<_> = heap.pop_while(|_, &keep| !keep).map(|(k, _)| k).collect();
+        assert_eq!(popped, [2, 4]);
+        assert_eq!(heap.min_key(), Some(&6));
+        assert_eq!(heap.len(), 2);
+    }
+
+    #[test]
+    fn peek_mut_edits_and_pops_the_top() {
+        let mut heap = HashedMinHeap::new();
+        heap.insert(2, 'b');
+        heap.insert(1, 'a');
+        *heap.peek_mut().unwrap() = 'z';
+        assert_eq!(heap.get(&1), Some(&'z'));
+        let top = heap.peek_mut().unwrap();
+        assert_eq!(top.key(), &1);
+        assert_eq!(PeekMut::pop(top), (1, 'z'));
+        assert_eq!(heap.peek(), Some((&2, &'b')));
+    }
+
+    #[test]
+    fn iter_sorted_and_top_k_leave_the_heap_untouched() {
+        let mut heap = HashedMaxHeap::new();
+        for key in [2, 9, 4, 7] {
+            heap.insert(key, key + 1);
+        }
+        let sorted: Vec<_> = heap.iter_sorted().map(|(&k, _)| k).collect();
+        assert_eq!(sorted, [9, 7, 4, 2]);
+        assert_eq!(heap.top_k(2), [(&9, &10), (&7, &8)]);
+        assert_eq!(heap.top_k(10).len(), 4);
+        assert_eq!(heap.len(), 4);
+        assert_eq!(heap.pop_max(), Some((9, 10)));
+    }
+
+    #[test]
+    fn dropping_drain_sorted_early_clears_the_rest() {
+        let mut heap = HashedMinHeap::new();
+        for key in [3, 1, 2] {
+            heap.insert(key, ());
+        }
+        assert_eq!(heap.drain_sorted().next(), Some((1, ())));
+        assert!(heap.is_empty());
+        assert!(!heap.contains_key(&3));
+        heap.insert(5, ());
+        assert_eq!(heap.into_sorted_vec(), [(5, ())]);
+    }
+
+    #[test]
+    fn min_max_heap_pops_and_removes_at_both_ends() {
+        let mut heap = HashedMinMaxHeap::new();
+        for key in [4, 8, 1, 6, 3] {
+            heap.insert(key, key * 2);
+        }
+        assert_eq!(heap.peek_min(), Some((&1, &2)));
+        assert_eq!(heap.peek_max(), Some((&8, &16)));
+        assert_eq!(heap.remove(&8), Some(16));
+        assert_eq!(heap.max_key(), Some(&6));
+        assert_eq!(heap.pop_max(), Some((6, 12)));
+        assert_eq!(heap.pop_min(), Some((1, 2)));
+        assert_eq!(heap.pop_max(), Some((4, 8)));
+        assert_eq!(heap.pop_min(), Some((3, 6)));
+        assert_eq!(heap.pop_max(), None);
+    }
+
+    fn pops_after_removals<B: HeapBackend>() -> Vec<u32> {
+        let mut heap = HashedHeap::<u32, (), Min<u32>, B>::new();
+        for key in [7, 3, 9, 1, 8, 2, 6] {
+            heap.insert(key, ());
+        }
+        heap.remove(&3);
+        heap.remove(&9);
+        heap.insert(5, ());
+        iter::from_fn(|| heap.pop_top()).map(|(k, _)| k).collect()
+    }
+
+    #[test]
+    fn every_backend_keeps_the_key_index_in_sync() {
+        let expected = [1, 2, 5, 6, 7, 8];
+        assert_eq!(pops_after_removals::<Binary>(), expected);
+        assert_eq!(pops_after_removals::<backend::DAry<4>>(), expected);
+        assert_eq!(pops_after_removals::<backend::Pairing>(), expected);
+        assert_eq!(pops_after_removals::<MinMax>(), expected);
+    }
+
+    thread_local! {
+        static CLONES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    struct Counted(u32);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.with(|clones| clones.set(clones.get() + 1));
+            Counted(self.0)
+        }
+    }
+
+    fn clones() -> usize {
+        CLONES.with(Cell::get)
+    }
+
+    fn entry_ref_clones_on_insert<M>(map: &mut M)
+    where
+        M: EntryRef<Counted, K = Counted, V = u32>,
+    {
+        map.insert(Counted(1), 10);
+        let before = clones();
+        assert_eq!(map.entry_ref(&Counted(1)).key(), &Counted(1));
+        *map.entry_ref(&Counted(1)).or_insert(0) += 1;
+        assert!(matches!(
+            map.entry_ref(&Counted(2)),
+            crate::Entry::Vacant(_)
+        ));
+        assert_eq!(clones(), before);
+        match map.entry_ref(&Counted(2)) {
+            crate::Entry::Vacant(vac) => {
+                assert_eq!(vac.key(), &Counted(2));
+                vac.insert(20);
+            }
+            crate::Entry::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(clones(), before + 1);
+        map.entry_ref(&Counted(3)).or_insert(30);
+        assert_eq!(clones(), before + 2);
+        assert_eq!(map.get(&Counted(1)), Some(&11));
+        assert_eq!(map.get(&Counted(2)), Some(&20));
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn entry_ref_clones_keys_only_on_insertion() {
+        entry_ref_clones_on_insert(&mut HashedMinHeap::new());
+        entry_ref_clones_on_insert(&mut StableHashedHeap::<_, _, Min<Counted>>::new());
+        let mut values = HashedValueMinHeap::new();
+        values.insert(Counted(1), 10);
+        let before = clones();
+        *values.entry_ref(&Counted(1)).or_insert(0) += 1;
+        assert!(matches!(
+            values.entry_ref(&Counted(2)),
+            crate::Entry::Vacant(_)
+        ));
+        assert_eq!(clones(), before);
+        values.entry_ref(&Counted(2)).or_insert(20);
+        assert_eq!(clones(), before + 2);
+        assert_eq!(values.pop_min(), Some((Counted(1), 11)));
     }
 }
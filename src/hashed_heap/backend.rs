@@ -0,0 +1,195 @@
+use std::ops::IndexMut;
+use std::vec;
+
+use super::comparator::Comparator;
+use super::indexed_heap::{Index, IndexedHeap};
+use super::min_max_heap::MinMaxHeap;
+use super::pairing_heap::PairingHeap;
+
+pub trait HeapBackend {
+    type Heap<T, C>: Heap<T, C>;
+}
+
+pub trait DoubleEndedBackend: HeapBackend {
+    fn bottom<T, C: Comparator<T>>(heap: &Self::Heap<T, C>) -> Option<Index>;
+}
+
+pub trait Heap<T, C>: IndexMut<Index, Output = T> {
+    type Sorted;
+
+    fn with_comparator(compare: C) -> Self;
+    fn comparator(&self) -> &C;
+    fn clear(&mut self);
+    fn as_slice(&self) -> &[T];
+    fn as_mut_slice(&mut self) -> &mut [T];
+    fn drain(&mut self) -> vec::Drain<'_, T>;
+    fn into_vec(self) -> Vec<T>;
+    fn reserve(&mut self, additional: usize);
+    fn peek(&self) -> Option<&T>;
+    fn insert<'a>(&'a mut self, value: T) -> (Index, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>;
+    fn push_unordered(&mut self, value: T) -> Index;
+    fn restore_from<'a>(&'a mut self, start: usize) -> impl Iterator<Item = (Index, &'a T)> + 'a
+    where
+        T: 'a,
+        C: Comparator<T>;
+    fn remove<'a>(&'a mut self, index: Index) -> (T, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>;
+    fn sorted(&self) -> Self::Sorted
+    where
+        C: Comparator<T>;
+    fn next_sorted(&self, sorted: &mut Self::Sorted) -> Option<Index>
+    where
+        C: Comparator<T>;
+}
+
+pub struct DAry<const D: usize>;
+
+pub type Binary = DAry<2>;
+
+impl<const D: usize> HeapBackend for DAry<D> {
+    type Heap<T, C> = IndexedHeap<T, C, D>;
+}
+
+pub struct Pairing;
+
+impl HeapBackend for Pairing {
+    type Heap<T, C> = PairingHeap<T, C>;
+}
+
+pub struct MinMax;
+
+impl HeapBackend for MinMax {
+    type Heap<T, C> = MinMaxHeap<T, C>;
+}
+
+impl DoubleEndedBackend for MinMax {
+    fn bottom<T, C: Comparator<T>>(heap: &MinMaxHeap<T, C>) -> Option<Index> {
+        heap.bottom()
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::hashed_heap::comparator::Min;
+
+    type Entry = (u32, usize);
+    type TestHeap<B> = <B as HeapBackend>::Heap<Entry, Min<Entry>>;
+
+    pub(in crate::hashed_heap) struct Tracked<B: HeapBackend> {
+        heap: TestHeap<B>,
+        positions: Vec<Option<Index>>,
+    }
+
+    impl<B: HeapBackend> Tracked<B> {
+        pub(in crate::hashed_heap) fn new(keys: &[u32]) -> Self {
+            let mut tracked = Self {
+                heap: TestHeap::<B>::with_comparator(Min::default()),
+                positions: Vec::new(),
+            };
+            for &key in keys {
+                tracked.insert(key);
+            }
+            tracked
+        }
+
+        pub(in crate::hashed_heap) fn insert(&mut self, key: u32) -> usize {
+            let id = self.positions.len();
+            self.positions.push(None);
+            let (index, changes) = self.heap.insert((key, id));
+            for (new_index, &(_, moved)) in changes {
+                self.positions[moved] = Some(new_index);
+            }
+            self.positions[id] = Some(index);
+            self.check();
+            id
+        }
+
+        pub(in crate::hashed_heap) fn remove(&mut self, id: usize) -> u32 {
+            let index = self.positions[id].take().unwrap();
+            let ((key, removed), changes) = self.heap.remove(index);
+            assert_eq!(removed, id);
+            for (new_index, &(_, moved)) in changes {
+                self.positions[moved] = Some(new_index);
+            }
+            self.check();
+            key
+        }
+
+        pub(in crate::hashed_heap) fn pop(&mut self) -> Option<u32> {
+            let &(_, id) = self.heap.peek()?;
+            Some(self.remove(id))
+        }
+
+        pub(in crate::hashed_heap) fn sorted(&self) -> Vec<u32> {
+            let mut sorted = self.heap.sorted();
+            std::iter::from_fn(|| self.heap.next_sorted(&mut sorted))
+                .map(|index| self.heap[index].0)
+                .collect()
+        }
+
+        fn check(&self) {
+            for (id, position) in self.positions.iter().enumerate() {
+                if let Some(index) = *position {
+                    assert_eq!(self.heap[index].1, id);
+                }
+            }
+        }
+    }
+
+    pub(in crate::hashed_heap) fn pops_in_order<B: HeapBackend>() {
+        let keys: Vec<_> = (0..40).map(|i| (i * 17) % 40).collect();
+        let mut heap = Tracked::<B>::new(&keys);
+        let expected: Vec<_> = (0..40).collect();
+        assert_eq!(heap.sorted(), expected);
+        let popped: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, expected);
+    }
+
+    pub(in crate::hashed_heap) fn removes_interior_entries<B: HeapBackend>() {
+        let keys: Vec<_> = (0..40).rev().collect();
+        let mut heap = Tracked::<B>::new(&keys);
+        for id in (1..40).step_by(3) {
+            heap.remove(id);
+        }
+        let expected: Vec<_> = (0..40).filter(|key| (39 - key) % 3 != 1).collect();
+        assert_eq!(heap.sorted(), expected);
+        let popped: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn binary_pops_in_order() {
+        pops_in_order::<Binary>();
+    }
+
+    #[test]
+    fn ternary_pops_in_order_with_partial_last_family() {
+        pops_in_order::<DAry<3>>();
+    }
+
+    #[test]
+    fn quaternary_removes_interior_entries() {
+        removes_interior_entries::<DAry<4>>();
+    }
+
+    #[test]
+    fn min_max_removes_interior_entries() {
+        removes_interior_entries::<MinMax>();
+    }
+
+    #[test]
+    fn removing_the_last_slot_reports_no_moves() {
+        let mut heap = Tracked::<Binary>::new(&[1, 2, 3]);
+        assert_eq!(heap.remove(2), 3);
+        assert_eq!(heap.remove(0), 1);
+        assert_eq!(heap.sorted(), [2]);
+        assert_eq!(heap.pop(), Some(2));
+        assert_eq!(heap.pop(), None);
+    }
+}
@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::marker::PhantomData;
 
 pub struct Max<T>(PhantomData<T>);
@@ -6,6 +7,12 @@ impl<T> Default for Max<T> {
         Self(PhantomData)
     }
 }
+impl<T> Clone for Max<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Max<T> {}
 
 pub struct Min<T>(PhantomData<T>);
 impl<T> Default for Min<T> {
@@ -13,8 +20,65 @@ impl<T> Default for Min<T> {
         Self(PhantomData)
     }
 }
+impl<T> Clone for Min<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Min<T> {}
+
+pub struct TotalMax<T>(PhantomData<T>);
+impl<T> Default for TotalMax<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+impl<T> Clone for TotalMax<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for TotalMax<T> {}
+
+pub struct TotalMin<T>(PhantomData<T>);
+impl<T> Default for TotalMin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+impl<T> Clone for TotalMin<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for TotalMin<T> {}
+
+#[derive(Clone, Copy, Default)]
+pub struct Reverse<C>(pub C);
+
+#[derive(Clone, Copy, Default)]
+pub struct Then<C1, C2>(pub C1, pub C2);
+
+#[derive(Clone, Copy, Default)]
+pub struct ByKey<F, C> {
+    key: F,
+    compare: C,
+}
+
+impl<F, C> ByKey<F, C> {
+    pub fn new(key: F) -> Self
+    where
+        C: Default,
+    {
+        Self::with_comparator(key, C::default())
+    }
+
+    pub fn with_comparator(key: F, compare: C) -> Self {
+        Self { key, compare }
+    }
+}
 
-pub trait Comparator<T>: Default {
+pub trait Comparator<T> {
     fn favors(&self, a: &T, b: &T) -> bool;
     fn favored<'a>(&self, a: &'a T, b: &'a T) -> &'a T {
         if self.favors(b, a) {
@@ -25,6 +89,22 @@ pub trait Comparator<T>: Default {
     }
 }
 
+pub trait TotalOrd {
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+impl TotalOrd for f32 {
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+}
+
+impl TotalOrd for f64 {
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+}
+
 impl<T: PartialOrd> Comparator<T> for Max<T> {
     fn favors(&self, a: &T, b: &T) -> bool {
         a > b
@@ -36,3 +116,33 @@ impl<T: PartialOrd> Comparator<T> for Min<T> {
         a < b
     }
 }
+
+impl<T: TotalOrd> Comparator<T> for TotalMax<T> {
+    fn favors(&self, a: &T, b: &T) -> bool {
+        a.total_cmp(b) == Ordering::Greater
+    }
+}
+
+impl<T: TotalOrd> Comparator<T> for TotalMin<T> {
+    fn favors(&self, a: &T, b: &T) -> bool {
+        a.total_cmp(b) == Ordering::Less
+    }
+}
+
+impl<T, C: Comparator<T>> Comparator<T> for Reverse<C> {
+    fn favors(&self, a: &T, b: &T) -> bool {
+        self.0.favors(b, a)
+    }
+}
+
+impl<T, C1: Comparator<T>, C2: Comparator<T>> Comparator<T> for Then<C1, C2> {
+    fn favors(&self, a: &T, b: &T) -> bool {
+        self.0.favors(a, b) || (!self.0.favors(b, a) && self.1.favors(a, b))
+    }
+}
+
+impl<T, U, F: Fn(&T) -> U, C: Comparator<U>> Comparator<T> for ByKey<F, C> {
+    fn favors(&self, a: &T, b: &T) -> bool {
+        self.compare.favors(&(self.key)(a), &(self.key)(b))
+    }
+}
@@ -1,54 +1,92 @@
-use std::collections::{hash_map, HashMap};
+use std::collections::hash_map::RandomState;
 use std::hash::Hash;
 use std::mem;
 
+use crate::entry_ref::{KeySource, PendingKey};
 use crate::{OccupiedEntry, VacantEntry};
 
+use super::backend::{Binary, HeapBackend};
 use super::comparator::Comparator;
-use super::indexed_heap::{Index, IndexedHeap};
+use super::HashedHeap;
 
-pub struct Entry<'a, K, V, C, E> {
-    map: *mut HashMap<K, (V, Index)>,
-    heap: &'a mut IndexedHeap<K, C>,
+pub struct Entry<'a, K, V, C, E, B: HeapBackend = Binary, S = RandomState> {
+    heap: &'a mut HashedHeap<K, V, C, B, S>,
     entry: E,
 }
 
-impl<'a, K, V, C, E> Entry<'a, K, V, C, E> {
-    pub(super) unsafe fn new(
-        map: *mut HashMap<K, (V, Index)>,
-        heap: &'a mut IndexedHeap<K, C>,
-        entry: E,
-    ) -> Self {
-        Self { map, heap, entry }
+pub struct Vacant<T> {
+    key: T,
+    hash: u64,
+}
+
+pub struct Occupied {
+    slot: usize,
+}
+
+impl<'a, K, V, C, T, B: HeapBackend, S> Entry<'a, K, V, C, Vacant<T>, B, S> {
+    pub(super) fn vacant(heap: &'a mut HashedHeap<K, V, C, B, S>, key: T, hash: u64) -> Self {
+        Self {
+            heap,
+            entry: Vacant { key, hash },
+        }
+    }
+
+    pub(super) fn source(&self) -> &T {
+        &self.entry.key
+    }
+
+    pub(super) fn map_key<U>(self, f: impl FnOnce(T) -> U) -> Entry<'a, K, V, C, Vacant<U>, B, S> {
+        let Vacant { key, hash } = self.entry;
+        Entry::vacant(self.heap, f(key), hash)
     }
 }
 
-pub type VacEntry<'a, K, V, C> = Entry<'a, K, V, C, hash_map::VacantEntry<'a, K, (V, Index)>>;
-pub type OccupEntry<'a, K, V, C> = Entry<'a, K, V, C, hash_map::OccupiedEntry<'a, K, (V, Index)>>;
+impl<'a, K, V, C, B: HeapBackend, S> OccupEntry<'a, K, V, C, B, S> {
+    pub(super) fn occupied(heap: &'a mut HashedHeap<K, V, C, B, S>, slot: usize) -> Self {
+        Self {
+            heap,
+            entry: Occupied { slot },
+        }
+    }
+}
 
-impl<'a, K, V, C> VacEntry<'a, K, V, C> {
+pub type VacEntry<'a, K, V, C, B = Binary, S = RandomState> = Entry<'a, K, V, C, Vacant<K>, B, S>;
+pub type VacEntryRef<'a, K, V, C, Q, B = Binary, S = RandomState> =
+    Entry<'a, K, V, C, Vacant<PendingKey<'a, K, Q>>, B, S>;
+pub type OccupEntry<'a, K, V, C, B = Binary, S = RandomState> = Entry<'a, K, V, C, Occupied, B, S>;
+
+impl<'a, K, V, C, T: KeySource<K>, B: HeapBackend, S> Entry<'a, K, V, C, Vacant<T>, B, S> {
     pub fn key(&self) -> &K {
-        self.entry.key()
+        self.entry.key.key()
     }
 
     pub fn insert(self, value: V) -> &'a mut V
     where
-        K: Eq + Hash + Clone,
         C: Comparator<K>,
     {
-        let (index, changed_indices) = self.heap.insert(self.entry.key().clone());
-        let result = &mut self.entry.insert((value, index)).0;
-        let map = unsafe { &mut *self.map };
-        for (new_index, k) in changed_indices {
-            map.get_mut(&k).unwrap().1 = new_index;
-        }
-        result
+        self.insert_entry(value).into_mut()
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V, C, B, S>
+    where
+        C: Comparator<K>,
+    {
+        let Vacant { key, hash } = self.entry;
+        let key = key.into_key();
+        let heap = self.heap;
+        let slot = heap.slots.len();
+        let slots = &heap.slots;
+        heap.table.insert_unique(hash, slot, |&s| slots[s].hash);
+        heap.push_slot(key, value, hash);
+        Entry::occupied(heap, slot)
     }
 }
 
-impl<'a, K: Eq + Hash + Clone, V, C: Comparator<K>> VacantEntry<'a, K, V>
-    for VacEntry<'a, K, V, C>
+impl<'a, K: Eq + Hash, V, C: Comparator<K>, T: KeySource<K>, B: HeapBackend, S>
+    VacantEntry<'a, K, V> for Entry<'a, K, V, C, Vacant<T>, B, S>
 {
+    type Occupied = OccupEntry<'a, K, V, C, B, S>;
+
     fn key(&self) -> &K {
         self.key()
     }
@@ -56,46 +94,58 @@ impl<'a, K: Eq + Hash + Clone, V, C: Comparator<K>> VacantEntry<'a, K, V>
     fn insert(self, value: V) -> &'a mut V {
         self.insert(value)
     }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
 }
 
-impl<'a, K, V, C> OccupEntry<'a, K, V, C> {
+impl<'a, K, V, C, B: HeapBackend, S> OccupEntry<'a, K, V, C, B, S> {
     pub fn key(&self) -> &K {
-        self.entry.key()
+        self.heap.key_at(self.entry.slot)
     }
 
     pub fn insert(&mut self, value: V) -> V {
-        mem::replace(&mut self.entry.get_mut().0, value)
+        mem::replace(self.get_mut(), value)
     }
 
     pub fn remove(self) -> V
     where
-        K: Eq + Hash,
         C: Comparator<K>,
     {
-        let (k1, (result, index)) = self.entry.remove_entry();
-        let (k2, changed_indices) = self.heap.remove(index);
-        assert!(k1 == k2);
-        let map = unsafe { &mut *self.map };
-        for (new_index, k) in changed_indices {
-            map.get_mut(&k).unwrap().1 = new_index;
-        }
-        result
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V)
+    where
+        C: Comparator<K>,
+    {
+        self.heap.unlink(self.entry.slot);
+        self.heap.remove_slot(self.entry.slot)
+    }
+
+    pub fn replace_entry(self, key: K, value: V) -> (K, V) {
+        let slot = &mut self.heap.slots[self.entry.slot];
+        let key = mem::replace(&mut self.heap.heap[slot.index].0, key);
+        (key, mem::replace(&mut slot.value, value))
     }
 
     pub fn get(&self) -> &V {
-        &self.entry.get().0
+        &self.heap.slots[self.entry.slot].value
     }
 
     pub fn get_mut(&mut self) -> &mut V {
-        &mut self.entry.get_mut().0
+        &mut self.heap.slots[self.entry.slot].value
     }
 
     pub fn into_mut(self) -> &'a mut V {
-        &mut self.entry.into_mut().0
+        &mut self.heap.slots[self.entry.slot].value
     }
 }
 
-impl<'a, K: Eq + Hash, V, C: Comparator<K>> OccupiedEntry<'a, K, V> for OccupEntry<'a, K, V, C> {
+impl<'a, K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend, S> OccupiedEntry<'a, K, V>
+    for OccupEntry<'a, K, V, C, B, S>
+{
     fn key(&self) -> &K {
         self.key()
     }
@@ -108,6 +158,14 @@ impl<'a, K: Eq + Hash, V, C: Comparator<K>> OccupiedEntry<'a, K, V> for OccupEnt
         self.remove()
     }
 
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
+    fn replace_entry(self, key: K, value: V) -> (K, V) {
+        self.replace_entry(key, value)
+    }
+
     fn get(&self) -> &V {
         self.get()
     }
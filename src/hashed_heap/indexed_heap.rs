@@ -1,25 +1,29 @@
+use std::ops::Range;
+use std::{slice, vec};
+
+use super::backend::Heap;
 use super::comparator::Comparator;
 
-pub struct IndexedHeap<T, C> {
+pub struct IndexedHeap<T, C, const D: usize = 2> {
     data: Vec<T>,
     changed_indices_scratch: Vec<Index>,
     compare: C,
 }
 
-impl<T, C: Default> Default for IndexedHeap<T, C> {
+impl<T, C: Default, const D: usize> Default for IndexedHeap<T, C, D> {
     fn default() -> Self {
-        Self {
-            data: Vec::new(),
-            changed_indices_scratch: Vec::new(),
-            compare: C::default(),
-        }
+        Self::with_comparator(C::default())
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
-pub struct Index(usize);
+pub struct Index(pub(super) usize);
 
-impl<T, C> std::ops::Index<Index> for IndexedHeap<T, C> {
+impl Index {
+    pub const TOP: Self = Self(0);
+}
+
+impl<T, C, const D: usize> std::ops::Index<Index> for IndexedHeap<T, C, D> {
     type Output = T;
 
     fn index(&self, index: Index) -> &Self::Output {
@@ -27,18 +31,54 @@ impl<T, C> std::ops::Index<Index> for IndexedHeap<T, C> {
     }
 }
 
-impl<T, C> IndexedHeap<T, C> {
-    pub fn new() -> Self
-    where
-        C: Default,
-    {
-        Self::default()
+impl<T, C, const D: usize> std::ops::IndexMut<Index> for IndexedHeap<T, C, D> {
+    fn index_mut(&mut self, index: Index) -> &mut Self::Output {
+        &mut self.data[index.0]
+    }
+}
+
+impl<T, C, const D: usize> IndexedHeap<T, C, D> {
+    pub fn with_comparator(compare: C) -> Self {
+        const { assert!(D >= 2, "heap arity must be at least 2") };
+        Self {
+            data: Vec::new(),
+            changed_indices_scratch: Vec::new(),
+            compare,
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.compare
     }
 
     pub fn clear(&mut self) {
         self.data.clear();
     }
 
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.data.iter_mut()
+    }
+
+    pub fn drain(&mut self) -> vec::Drain<'_, T> {
+        self.data.drain(..)
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
     pub fn insert(&mut self, value: T) -> (Index, impl Iterator<Item = (Index, &'_ T)> + '_)
     where
         C: Comparator<T>,
@@ -54,6 +94,40 @@ impl<T, C> IndexedHeap<T, C> {
         )
     }
 
+    pub fn push_unordered(&mut self, value: T) -> Index {
+        self.data.push(value);
+        Index(self.data.len() - 1)
+    }
+
+    pub fn restore_from(&mut self, start: usize) -> impl Iterator<Item = (Index, &'_ T)> + '_
+    where
+        C: Comparator<T>,
+    {
+        let len = self.data.len();
+        let log_len = (usize::BITS - len.leading_zeros()) as usize;
+        let heapify = (len - start) * log_len >= 2 * len;
+        if heapify {
+            for i in (0..(len + D - 2) / D).rev() {
+                self.sift_down(&mut Index(i));
+            }
+            self.changed_indices_scratch.clear();
+        } else {
+            for i in start..len {
+                let mut index = Index(i);
+                self.sift_up(&mut index);
+                self.changed_indices_scratch.push(index);
+            }
+        }
+        let all = if heapify { 0..len } else { 0..0 };
+        all.map(Index)
+            .chain(self.changed_indices_scratch.drain(..))
+            .map(|i| (i, &self.data[i.0]))
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+    }
+
     pub fn remove(&mut self, mut index: Index) -> (T, impl Iterator<Item = (Index, &'_ T)> + '_)
     where
         C: Comparator<T>,
@@ -74,8 +148,46 @@ impl<T, C> IndexedHeap<T, C> {
         )
     }
 
+    pub fn update(&mut self, mut index: Index) -> impl Iterator<Item = (Index, &'_ T)> + '_
+    where
+        C: Comparator<T>,
+    {
+        self.sift_up(&mut index);
+        self.sift_down(&mut index);
+        self.changed_indices_scratch.push(index);
+        self.changed_indices_scratch
+            .drain(..)
+            .map(|i| (i, &self.data[i.0]))
+    }
+
+    pub fn rebuild(&mut self) -> impl Iterator<Item = (Index, &'_ T)> + '_
+    where
+        C: Comparator<T>,
+    {
+        for i in (0..(self.data.len() + D - 2) / D).rev() {
+            self.sift_down(&mut Index(i));
+        }
+        self.changed_indices_scratch.clear();
+        self.data.iter().enumerate().map(|(i, v)| (Index(i), v))
+    }
+
+    pub fn sorted_indices(&self) -> SortedIndices {
+        SortedIndices::new((!self.data.is_empty()).then_some(Index::TOP))
+    }
+
+    fn children(&self, index: Index) -> impl Iterator<Item = Index> {
+        children::<D>(index, self.data.len()).map(Index)
+    }
+
     pub fn peek(&self) -> Option<&T> {
-        self.data.get(0)
+        self.data.first()
+    }
+
+    fn favors(&self, a: Index, b: Index) -> bool
+    where
+        C: Comparator<T>,
+    {
+        self.compare.favors(&self[a], &self[b])
     }
 
     fn swap(&mut self, a: Index, b: Index) {
@@ -86,7 +198,7 @@ impl<T, C> IndexedHeap<T, C> {
     where
         C: Comparator<T>,
     {
-        while let Some(parent) = parent(*index) {
+        while let Some(parent) = parent::<D>(*index) {
             if self.compare.favors(&self[*index], &self[parent]) {
                 self.swap(parent, *index);
                 self.changed_indices_scratch.push(*index);
@@ -101,37 +213,168 @@ impl<T, C> IndexedHeap<T, C> {
     where
         C: Comparator<T>,
     {
+        while let Some(favored_child) =
+            self.children(*index)
+                .reduce(|a, b| if self.favors(b, a) { b } else { a })
+        {
+            if self.favors(favored_child, *index) {
+                self.swap(favored_child, *index);
+                self.changed_indices_scratch.push(*index);
+                *index = favored_child;
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+pub struct SortedIndices {
+    frontier: Vec<Index>,
+}
+
+impl SortedIndices {
+    pub fn new(root: Option<Index>) -> Self {
+        Self {
+            frontier: root.into_iter().collect(),
+        }
+    }
+
+    pub fn next<I: IntoIterator<Item = Index>>(
+        &mut self,
+        favors: impl Fn(Index, Index) -> bool,
+        children: impl FnOnce(Index) -> I,
+    ) -> Option<Index> {
+        let index = self.pop(&favors)?;
+        for child in children(index) {
+            self.push(&favors, child);
+        }
+        Some(index)
+    }
+
+    fn push(&mut self, favors: impl Fn(Index, Index) -> bool, index: Index) {
+        let mut pos = self.frontier.len();
+        self.frontier.push(index);
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if !favors(self.frontier[pos], self.frontier[parent]) {
+                break;
+            }
+            self.frontier.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    fn pop(&mut self, favors: impl Fn(Index, Index) -> bool) -> Option<Index> {
+        let last = self.frontier.len().checked_sub(1)?;
+        self.frontier.swap(0, last);
+        let result = self.frontier.pop();
+        let mut pos = 0;
         loop {
-            let (left, right) = children(*index);
-            let mut favored_child = left;
-            if right.0 < self.data.len() {
-                if self.compare.favors(&self[right], &self[left]) {
-                    favored_child = right;
-                }
+            let (left, right) = (pos * 2 + 1, pos * 2 + 2);
+            let mut favored = pos;
+            if left < self.frontier.len() && favors(self.frontier[left], self.frontier[favored]) {
+                favored = left;
             }
-            if favored_child.0 < self.data.len() {
-                if self.compare.favors(&self[favored_child], &self[*index]) {
-                    self.swap(favored_child, *index);
-                    self.changed_indices_scratch.push(*index);
-                    *index = favored_child;
-                } else {
-                    break;
-                }
-            } else {
+            if right < self.frontier.len() && favors(self.frontier[right], self.frontier[favored]) {
+                favored = right;
+            }
+            if favored == pos {
                 break;
             }
+            self.frontier.swap(pos, favored);
+            pos = favored;
         }
+        result
     }
 }
 
-fn parent(index: Index) -> Option<Index> {
-    if index.0 == 0 {
-        None
-    } else {
-        Some(Index((index.0 - 1) / 2))
+impl<T, C, const D: usize> Heap<T, C> for IndexedHeap<T, C, D> {
+    type Sorted = SortedIndices;
+
+    fn with_comparator(compare: C) -> Self {
+        IndexedHeap::with_comparator(compare)
+    }
+
+    fn comparator(&self) -> &C {
+        IndexedHeap::comparator(self)
+    }
+
+    fn clear(&mut self) {
+        IndexedHeap::clear(self)
+    }
+
+    fn as_slice(&self) -> &[T] {
+        IndexedHeap::as_slice(self)
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        IndexedHeap::as_mut_slice(self)
+    }
+
+    fn drain(&mut self) -> vec::Drain<'_, T> {
+        IndexedHeap::drain(self)
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        IndexedHeap::into_vec(self)
     }
+
+    fn reserve(&mut self, additional: usize) {
+        IndexedHeap::reserve(self, additional)
+    }
+
+    fn peek(&self) -> Option<&T> {
+        IndexedHeap::peek(self)
+    }
+
+    fn insert<'a>(&'a mut self, value: T) -> (Index, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        IndexedHeap::insert(self, value)
+    }
+
+    fn push_unordered(&mut self, value: T) -> Index {
+        IndexedHeap::push_unordered(self, value)
+    }
+
+    fn restore_from<'a>(&'a mut self, start: usize) -> impl Iterator<Item = (Index, &'a T)> + 'a
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        IndexedHeap::restore_from(self, start)
+    }
+
+    fn remove<'a>(&'a mut self, index: Index) -> (T, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        IndexedHeap::remove(self, index)
+    }
+
+    fn sorted(&self) -> SortedIndices
+    where
+        C: Comparator<T>,
+    {
+        self.sorted_indices()
+    }
+
+    fn next_sorted(&self, sorted: &mut SortedIndices) -> Option<Index>
+    where
+        C: Comparator<T>,
+    {
+        sorted.next(|a, b| self.favors(a, b), |index| self.children(index))
+    }
+}
+
+fn parent<const D: usize>(index: Index) -> Option<Index> {
+    index.0.checked_sub(1).map(|i| Index(i / D))
 }
 
-fn children(index: Index) -> (Index, Index) {
-    (Index(index.0 * 2 + 1), Index(index.0 * 2 + 2))
+fn children<const D: usize>(index: Index, len: usize) -> Range<usize> {
+    let first = index.0 * D + 1;
+    first.min(len)..(first + D).min(len)
 }
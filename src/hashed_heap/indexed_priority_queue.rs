@@ -0,0 +1,244 @@
+use std::mem;
+
+use crate::clear::Clear;
+
+use super::comparator::Comparator;
+use super::indexed_heap::{Index, IndexedHeap};
+use super::KeyOrder;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Handle {
+    slot: usize,
+    generation: u64,
+}
+
+struct Slot {
+    generation: u64,
+    index: Option<Index>,
+}
+
+pub struct IndexedPriorityQueue<T, C> {
+    heap: IndexedHeap<(T, usize), KeyOrder<C>>,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+}
+
+fn reindex<'a, T: 'a>(slots: &mut [Slot], changes: impl Iterator<Item = (Index, &'a (T, usize))>) {
+    for (new_index, &(_, slot)) in changes {
+        slots[slot].index = Some(new_index);
+    }
+}
+
+fn release(slots: &mut [Slot], free: &mut Vec<usize>, slot: usize) {
+    let entry = &mut slots[slot];
+    entry.index = None;
+    entry.generation += 1;
+    free.push(slot);
+}
+
+impl<T, C: Default> Default for IndexedPriorityQueue<T, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, C> IndexedPriorityQueue<T, C> {
+    pub fn new() -> Self
+    where
+        C: Default,
+    {
+        Self::with_comparator(C::default())
+    }
+
+    pub fn with_comparator(compare: C) -> Self {
+        Self {
+            heap: IndexedHeap::with_comparator(KeyOrder(compare)),
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.heap.comparator().0
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.as_slice().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.as_slice().is_empty()
+    }
+
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.index_of(handle).is_some()
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        Some(&self.heap[self.index_of(handle)?].0)
+    }
+
+    pub fn push(&mut self, value: T) -> Handle
+    where
+        C: Comparator<T>,
+    {
+        let slot = self.free.pop().unwrap_or_else(|| {
+            self.slots.push(Slot {
+                generation: 0,
+                index: None,
+            });
+            self.slots.len() - 1
+        });
+        let (index, changes) = self.heap.insert((value, slot));
+        reindex(&mut self.slots, changes);
+        self.slots[slot].index = Some(index);
+        self.handle(slot)
+    }
+
+    pub fn peek(&self) -> Option<(Handle, &T)> {
+        let (value, slot) = self.heap.peek()?;
+        Some((self.handle(*slot), value))
+    }
+
+    pub fn pop(&mut self) -> Option<(Handle, T)>
+    where
+        C: Comparator<T>,
+    {
+        let &(_, slot) = self.heap.peek()?;
+        let handle = self.handle(slot);
+        Some((handle, self.take(slot)))
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T>
+    where
+        C: Comparator<T>,
+    {
+        self.index_of(handle)?;
+        Some(self.take(handle.slot))
+    }
+
+    pub fn update(&mut self, handle: Handle, value: T) -> Option<T>
+    where
+        C: Comparator<T>,
+    {
+        let index = self.index_of(handle)?;
+        let old = mem::replace(&mut self.heap[index].0, value);
+        reindex(&mut self.slots, self.heap.update(index));
+        Some(old)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.heap
+            .iter()
+            .map(|(value, slot)| (self.handle(*slot), value))
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = (Handle, T)> + '_ {
+        self.release_all();
+        let slots = &self.slots;
+        self.heap.drain().map(move |(value, slot)| {
+            let handle = Handle {
+                slot,
+                generation: slots[slot].generation - 1,
+            };
+            (handle, value)
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.release_all();
+        self.heap.clear();
+    }
+
+    fn handle(&self, slot: usize) -> Handle {
+        Handle {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    fn index_of(&self, handle: Handle) -> Option<Index> {
+        let slot = self.slots.get(handle.slot)?;
+        if slot.generation == handle.generation {
+            slot.index
+        } else {
+            None
+        }
+    }
+
+    fn release_all(&mut self) {
+        for &(_, slot) in self.heap.iter() {
+            release(&mut self.slots, &mut self.free, slot);
+        }
+    }
+
+    fn take(&mut self, slot: usize) -> T
+    where
+        C: Comparator<T>,
+    {
+        let index = self.slots[slot].index.unwrap();
+        release(&mut self.slots, &mut self.free, slot);
+        let ((value, _), changes) = self.heap.remove(index);
+        reindex(&mut self.slots, changes);
+        value
+    }
+}
+
+impl<T, C> Clear for IndexedPriorityQueue<T, C> {
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::comparator::Min;
+    use super::*;
+
+    fn queue() -> IndexedPriorityQueue<u32, Min<u32>> {
+        IndexedPriorityQueue::new()
+    }
+
+    #[test]
+    fn handles_follow_their_values_through_sifts() {
+        let mut queue = queue();
+        let handles: Vec<_> = [50, 10, 40, 20, 30].map(|v| queue.push(v)).into();
+        assert_eq!(queue.update(handles[2], 5), Some(40));
+        assert_eq!(queue.update(handles[1], 60), Some(10));
+        assert_eq!(queue.remove(handles[3]), Some(20));
+        assert_eq!(queue.peek(), Some((handles[2], &5)));
+        assert_eq!(queue.pop(), Some((handles[2], 5)));
+        assert_eq!(queue.pop(), Some((handles[4], 30)));
+        assert_eq!(queue.get(handles[1]), Some(&60));
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn stale_handles_do_not_reach_reused_slots() {
+        let mut queue = queue();
+        let first = queue.push(1);
+        assert_eq!(queue.pop(), Some((first, 1)));
+        let second = queue.push(2);
+        assert_ne!(first, second);
+        assert!(!queue.contains(first));
+        assert_eq!(queue.get(first), None);
+        assert_eq!(queue.update(first, 0), None);
+        assert_eq!(queue.remove(first), None);
+        assert_eq!(queue.get(second), Some(&2));
+    }
+
+    #[test]
+    fn drain_and_clear_invalidate_every_handle() {
+        let mut queue = queue();
+        let a = queue.push(3);
+        let b = queue.push(4);
+        let drained: Vec<_> = queue.drain().collect();
+        assert_eq!(drained.len(), 2);
+        assert!(drained.contains(&(a, 3)) && drained.contains(&(b, 4)));
+        assert!(!queue.contains(a) && !queue.contains(b));
+        let c = queue.push(7);
+        queue.clear();
+        assert!(!queue.contains(c));
+        assert!(queue.is_empty());
+    }
+}
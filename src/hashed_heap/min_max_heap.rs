@@ -0,0 +1,357 @@
+use std::vec;
+
+use super::backend::Heap;
+use super::comparator::Comparator;
+use super::indexed_heap::{Index, SortedIndices};
+
+pub struct MinMaxHeap<T, C> {
+    data: Vec<T>,
+    changed_indices_scratch: Vec<Index>,
+    compare: C,
+}
+
+impl<T, C> std::ops::Index<Index> for MinMaxHeap<T, C> {
+    type Output = T;
+
+    fn index(&self, index: Index) -> &Self::Output {
+        &self.data[index.0]
+    }
+}
+
+impl<T, C> std::ops::IndexMut<Index> for MinMaxHeap<T, C> {
+    fn index_mut(&mut self, index: Index) -> &mut Self::Output {
+        &mut self.data[index.0]
+    }
+}
+
+impl<T, C> MinMaxHeap<T, C> {
+    pub fn bottom(&self) -> Option<Index>
+    where
+        C: Comparator<T>,
+    {
+        match self.data.len() {
+            0 => None,
+            1 => Some(Index(0)),
+            2 => Some(Index(1)),
+            _ if self.favors(1, 2) => Some(Index(2)),
+            _ => Some(Index(1)),
+        }
+    }
+
+    fn favors(&self, a: usize, b: usize) -> bool
+    where
+        C: Comparator<T>,
+    {
+        self.compare.favors(&self.data[a], &self.data[b])
+    }
+
+    fn precedes(&self, min_level: bool, a: usize, b: usize) -> bool
+    where
+        C: Comparator<T>,
+    {
+        if min_level {
+            self.favors(a, b)
+        } else {
+            self.favors(b, a)
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.data.swap(a, b);
+        self.changed_indices_scratch.push(Index(a));
+        self.changed_indices_scratch.push(Index(b));
+    }
+
+    fn bubble_up(&mut self, index: usize) -> usize
+    where
+        C: Comparator<T>,
+    {
+        let min_level = is_min_level(index);
+        match parent(index) {
+            Some(parent) if self.precedes(!min_level, index, parent) => {
+                self.swap(index, parent);
+                self.bubble_up_levels(parent, !min_level)
+            }
+            _ => self.bubble_up_levels(index, min_level),
+        }
+    }
+
+    fn update(&mut self, index: usize)
+    where
+        C: Comparator<T>,
+    {
+        let min_level = is_min_level(index);
+        match parent(index) {
+            Some(parent) if self.precedes(!min_level, index, parent) => {
+                self.swap(index, parent);
+                self.trickle_down(index);
+                self.bubble_up_levels(parent, !min_level);
+            }
+            _ => {
+                if self.bubble_up_levels(index, min_level) == index {
+                    self.trickle_down(index);
+                }
+            }
+        }
+    }
+
+    fn bubble_up_levels(&mut self, mut index: usize, min_level: bool) -> usize
+    where
+        C: Comparator<T>,
+    {
+        while let Some(grandparent) = parent(index).and_then(parent) {
+            if !self.precedes(min_level, index, grandparent) {
+                break;
+            }
+            self.swap(index, grandparent);
+            index = grandparent;
+        }
+        index
+    }
+
+    fn successors(&self, index: usize) -> impl Iterator<Item = usize> {
+        let len = if is_min_level(index) {
+            self.data.len()
+        } else {
+            0
+        };
+        let first_child = index * 2 + 1;
+        let first_grandchild = index * 4 + 3;
+        (first_child..first_child + 2)
+            .chain(first_grandchild..first_grandchild + 4)
+            .take_while(move |&i| i < len)
+    }
+
+    fn trickle_down(&mut self, mut index: usize)
+    where
+        C: Comparator<T>,
+    {
+        let min_level = is_min_level(index);
+        loop {
+            let first_child = index * 2 + 1;
+            let first_grandchild = index * 4 + 3;
+            let Some(best) = (first_child..first_child + 2)
+                .chain(first_grandchild..first_grandchild + 4)
+                .take_while(|&i| i < self.data.len())
+                .reduce(|best, i| {
+                    if self.precedes(min_level, i, best) {
+                        i
+                    } else {
+                        best
+                    }
+                })
+            else {
+                return;
+            };
+            if !self.precedes(min_level, best, index) {
+                return;
+            }
+            self.swap(best, index);
+            if best < first_grandchild {
+                return;
+            }
+            let parent = (best - 1) / 2;
+            if self.precedes(!min_level, best, parent) {
+                self.swap(best, parent);
+            }
+            index = best;
+        }
+    }
+}
+
+impl<T, C> Heap<T, C> for MinMaxHeap<T, C> {
+    type Sorted = SortedIndices;
+
+    fn with_comparator(compare: C) -> Self {
+        Self {
+            data: Vec::new(),
+            changed_indices_scratch: Vec::new(),
+            compare,
+        }
+    }
+
+    fn comparator(&self) -> &C {
+        &self.compare
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    fn drain(&mut self) -> vec::Drain<'_, T> {
+        self.data.drain(..)
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    fn insert<'a>(&'a mut self, value: T) -> (Index, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        self.data.push(value);
+        let index = self.bubble_up(self.data.len() - 1);
+        (
+            Index(index),
+            self.changed_indices_scratch
+                .drain(..)
+                .map(|i| (i, &self.data[i.0])),
+        )
+    }
+
+    fn push_unordered(&mut self, value: T) -> Index {
+        self.data.push(value);
+        Index(self.data.len() - 1)
+    }
+
+    fn restore_from<'a>(&'a mut self, start: usize) -> impl Iterator<Item = (Index, &'a T)> + 'a
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        let len = self.data.len();
+        let log_len = (usize::BITS - len.leading_zeros()) as usize;
+        let heapify = (len - start) * log_len >= 2 * len;
+        if heapify {
+            for i in (0..len / 2).rev() {
+                self.trickle_down(i);
+            }
+            self.changed_indices_scratch.clear();
+        } else {
+            for i in start..len {
+                let index = self.bubble_up(i);
+                self.changed_indices_scratch.push(Index(index));
+            }
+        }
+        let all = if heapify { 0..len } else { 0..0 };
+        all.map(Index)
+            .chain(self.changed_indices_scratch.drain(..))
+            .map(|i| (i, &self.data[i.0]))
+    }
+
+    fn remove<'a>(&'a mut self, index: Index) -> (T, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        let value = self.data.swap_remove(index.0);
+        if index.0 < self.data.len() {
+            self.update(index.0);
+            self.changed_indices_scratch.push(index);
+        }
+        (
+            value,
+            self.changed_indices_scratch
+                .drain(..)
+                .map(|i| (i, &self.data[i.0])),
+        )
+    }
+
+    fn sorted(&self) -> SortedIndices
+    where
+        C: Comparator<T>,
+    {
+        SortedIndices::new((!self.data.is_empty()).then_some(Index::TOP))
+    }
+
+    fn next_sorted(&self, sorted: &mut SortedIndices) -> Option<Index>
+    where
+        C: Comparator<T>,
+    {
+        sorted.next(
+            |a, b| self.favors(a.0, b.0),
+            |index| self.successors(index.0).map(Index),
+        )
+    }
+}
+
+fn is_min_level(index: usize) -> bool {
+    (index + 1).ilog2().is_multiple_of(2)
+}
+
+fn parent(index: usize) -> Option<usize> {
+    index.checked_sub(1).map(|i| i / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use super::super::comparator::Min;
+    use super::*;
+
+    fn filled(values: &[u32]) -> MinMaxHeap<u32, Min<u32>> {
+        let mut heap = MinMaxHeap::with_comparator(Min::default());
+        for &value in values {
+            push(&mut heap, value);
+        }
+        heap
+    }
+
+    fn push(heap: &mut MinMaxHeap<u32, Min<u32>>, value: u32) {
+        heap.insert(value).1.for_each(drop);
+    }
+
+    fn pop_top(heap: &mut MinMaxHeap<u32, Min<u32>>) -> Option<u32> {
+        heap.peek()?;
+        Some(heap.remove(Index::TOP).0)
+    }
+
+    fn pop_bottom(heap: &mut MinMaxHeap<u32, Min<u32>>) -> Option<u32> {
+        let bottom = heap.bottom()?;
+        Some(heap.remove(bottom).0)
+    }
+
+    #[test]
+    fn bottom_of_tiny_heaps() {
+        let mut heap = filled(&[]);
+        assert!(heap.bottom().is_none());
+        push(&mut heap, 4);
+        assert!(heap.bottom() == Some(Index::TOP));
+        push(&mut heap, 2);
+        assert_eq!(heap[heap.bottom().unwrap()], 4);
+        push(&mut heap, 3);
+        assert_eq!(heap[heap.bottom().unwrap()], 4);
+    }
+
+    #[test]
+    fn alternating_pops_take_from_both_ends() {
+        let mut heap = filled(&[6, 1, 9, 3, 7, 2, 8, 5, 4]);
+        let mut popped = Vec::new();
+        while let Some(min) = pop_top(&mut heap) {
+            popped.push(min);
+            popped.extend(pop_bottom(&mut heap));
+        }
+        assert_eq!(popped, [1, 9, 2, 8, 3, 7, 4, 6, 5]);
+    }
+
+    #[test]
+    fn removing_from_max_and_min_levels_keeps_order() {
+        let mut heap = filled(&[11, 3, 14, 0, 9, 6, 12, 1, 8, 13, 5, 2, 10, 7, 4]);
+        let mut removed = vec![heap.remove(Index(1)).0];
+        removed.push(heap.remove(Index(4)).0);
+        removed.push(heap.remove(Index(6)).0);
+        let mut expected: Vec<_> = (0..15).filter(|v| !removed.contains(v)).collect();
+        assert_eq!(pop_bottom(&mut heap), expected.pop());
+        let rest: Vec<_> = iter::from_fn(|| pop_top(&mut heap)).collect();
+        assert_eq!(rest, expected);
+    }
+}
@@ -0,0 +1,302 @@
+use std::vec;
+
+use super::backend::Heap;
+use super::comparator::Comparator;
+use super::indexed_heap::{Index, SortedIndices};
+
+pub struct PairingHeap<T, C> {
+    data: Vec<T>,
+    links: Vec<Links>,
+    root: Option<usize>,
+    changed_indices_scratch: Vec<Index>,
+    pairs_scratch: Vec<usize>,
+    compare: C,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Links {
+    child: Option<usize>,
+    next: Option<usize>,
+    prev: Option<usize>,
+}
+
+impl<T, C> std::ops::Index<Index> for PairingHeap<T, C> {
+    type Output = T;
+
+    fn index(&self, index: Index) -> &Self::Output {
+        &self.data[index.0]
+    }
+}
+
+impl<T, C> std::ops::IndexMut<Index> for PairingHeap<T, C> {
+    fn index_mut(&mut self, index: Index) -> &mut Self::Output {
+        &mut self.data[index.0]
+    }
+}
+
+impl<T, C> PairingHeap<T, C> {
+    fn favors(&self, a: usize, b: usize) -> bool
+    where
+        C: Comparator<T>,
+    {
+        self.compare.favors(&self.data[a], &self.data[b])
+    }
+
+    fn children(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut child = self.links[node].child;
+        std::iter::from_fn(move || {
+            let result = child?;
+            child = self.links[result].next;
+            Some(result)
+        })
+    }
+
+    fn meld(&mut self, a: usize, b: usize) -> usize
+    where
+        C: Comparator<T>,
+    {
+        let (parent, child) = if self.favors(b, a) { (b, a) } else { (a, b) };
+        let first = self.links[parent].child;
+        if let Some(first) = first {
+            self.links[first].prev = Some(child);
+        }
+        self.links[child].next = first;
+        self.links[child].prev = Some(parent);
+        self.links[parent].child = Some(child);
+        parent
+    }
+
+    fn meld_into_root(&mut self, node: usize)
+    where
+        C: Comparator<T>,
+    {
+        self.root = Some(match self.root {
+            Some(root) => self.meld(root, node),
+            None => node,
+        });
+    }
+
+    fn detach(&mut self, node: usize) {
+        self.links[node].prev = None;
+        self.links[node].next = None;
+    }
+
+    fn merge_pairs(&mut self, first: Option<usize>) -> Option<usize>
+    where
+        C: Comparator<T>,
+    {
+        let mut next = first;
+        while let Some(a) = next {
+            let Some(b) = self.links[a].next else {
+                self.detach(a);
+                self.pairs_scratch.push(a);
+                break;
+            };
+            next = self.links[b].next;
+            self.detach(a);
+            self.detach(b);
+            let pair = self.meld(a, b);
+            self.pairs_scratch.push(pair);
+        }
+        let mut result = self.pairs_scratch.pop()?;
+        while let Some(pair) = self.pairs_scratch.pop() {
+            result = self.meld(pair, result);
+        }
+        Some(result)
+    }
+
+    fn cut(&mut self, node: usize) {
+        let Links { next, prev, .. } = self.links[node];
+        let prev = prev.unwrap();
+        if self.links[prev].child == Some(node) {
+            self.links[prev].child = next;
+        } else {
+            self.links[prev].next = next;
+        }
+        if let Some(next) = next {
+            self.links[next].prev = Some(prev);
+        }
+        self.detach(node);
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) {
+        let Links { child, next, prev } = self.links[to];
+        if let Some(prev) = prev {
+            if self.links[prev].child == Some(from) {
+                self.links[prev].child = Some(to);
+            } else {
+                self.links[prev].next = Some(to);
+            }
+        }
+        if let Some(next) = next {
+            self.links[next].prev = Some(to);
+        }
+        if let Some(child) = child {
+            self.links[child].prev = Some(to);
+        }
+        if self.root == Some(from) {
+            self.root = Some(to);
+        }
+    }
+}
+
+impl<T, C> Heap<T, C> for PairingHeap<T, C> {
+    type Sorted = SortedIndices;
+
+    fn with_comparator(compare: C) -> Self {
+        Self {
+            data: Vec::new(),
+            links: Vec::new(),
+            root: None,
+            changed_indices_scratch: Vec::new(),
+            pairs_scratch: Vec::new(),
+            compare,
+        }
+    }
+
+    fn comparator(&self) -> &C {
+        &self.compare
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+        self.links.clear();
+        self.root = None;
+    }
+
+    fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    fn drain(&mut self) -> vec::Drain<'_, T> {
+        self.links.clear();
+        self.root = None;
+        self.data.drain(..)
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.links.reserve(additional);
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.root.map(|root| &self.data[root])
+    }
+
+    fn insert<'a>(&'a mut self, value: T) -> (Index, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        let index = self.push_unordered(value);
+        self.meld_into_root(index.0);
+        (
+            index,
+            self.changed_indices_scratch
+                .drain(..)
+                .map(|i| (i, &self.data[i.0])),
+        )
+    }
+
+    fn push_unordered(&mut self, value: T) -> Index {
+        self.data.push(value);
+        self.links.push(Links::default());
+        Index(self.data.len() - 1)
+    }
+
+    fn restore_from<'a>(&'a mut self, start: usize) -> impl Iterator<Item = (Index, &'a T)> + 'a
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        for node in start..self.data.len() {
+            self.meld_into_root(node);
+        }
+        self.changed_indices_scratch
+            .drain(..)
+            .map(|i| (i, &self.data[i.0]))
+    }
+
+    fn remove<'a>(&'a mut self, index: Index) -> (T, impl Iterator<Item = (Index, &'a T)> + 'a)
+    where
+        T: 'a,
+        C: Comparator<T>,
+    {
+        let node = index.0;
+        let children = self.links[node].child.take();
+        if self.root == Some(node) {
+            self.root = self.merge_pairs(children);
+        } else {
+            self.cut(node);
+            if let Some(subtree) = self.merge_pairs(children) {
+                self.meld_into_root(subtree);
+            }
+        }
+        let last = self.data.len() - 1;
+        let value = self.data.swap_remove(node);
+        self.links.swap_remove(node);
+        if node != last {
+            self.relocate(last, node);
+            self.changed_indices_scratch.push(index);
+        }
+        (
+            value,
+            self.changed_indices_scratch
+                .drain(..)
+                .map(|i| (i, &self.data[i.0])),
+        )
+    }
+
+    fn sorted(&self) -> SortedIndices
+    where
+        C: Comparator<T>,
+    {
+        SortedIndices::new(self.root.map(Index))
+    }
+
+    fn next_sorted(&self, sorted: &mut SortedIndices) -> Option<Index>
+    where
+        C: Comparator<T>,
+    {
+        sorted.next(
+            |a, b| self.favors(a.0, b.0),
+            |index| self.children(index.0).map(Index),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::backend::tests::{pops_in_order, removes_interior_entries, Tracked};
+    use super::super::backend::Pairing;
+
+    #[test]
+    fn pops_in_order_after_pairing_passes() {
+        pops_in_order::<Pairing>();
+    }
+
+    #[test]
+    fn removes_nodes_below_the_root() {
+        removes_interior_entries::<Pairing>();
+    }
+
+    #[test]
+    fn removes_children_of_a_wide_root() {
+        let mut heap = Tracked::<Pairing>::new(&[0, 5, 4, 3, 2, 1]);
+        assert_eq!(heap.remove(3), 3);
+        assert_eq!(heap.remove(5), 1);
+        assert_eq!(heap.pop(), Some(0));
+        let id = heap.insert(3);
+        assert_eq!(heap.sorted(), [2, 3, 4, 5]);
+        assert_eq!(heap.remove(id), 3);
+        assert_eq!(heap.sorted(), [2, 4, 5]);
+    }
+}
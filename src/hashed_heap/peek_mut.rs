@@ -0,0 +1,43 @@
+use std::collections::hash_map::RandomState;
+use std::ops::{Deref, DerefMut};
+
+use super::backend::{Binary, HeapBackend};
+use super::comparator::Comparator;
+use super::HashedHeap;
+
+pub struct PeekMut<'a, K, V, C, B: HeapBackend = Binary, S = RandomState> {
+    heap: &'a mut HashedHeap<K, V, C, B, S>,
+    slot: usize,
+}
+
+impl<'a, K, V, C, B: HeapBackend, S> PeekMut<'a, K, V, C, B, S> {
+    pub(super) fn new(heap: &'a mut HashedHeap<K, V, C, B, S>, slot: usize) -> Self {
+        Self { heap, slot }
+    }
+
+    pub fn key(&self) -> &K {
+        self.heap.key_at(self.slot)
+    }
+
+    pub fn pop(this: Self) -> (K, V)
+    where
+        C: Comparator<K>,
+    {
+        this.heap.unlink(this.slot);
+        this.heap.remove_slot(this.slot)
+    }
+}
+
+impl<'a, K, V, C, B: HeapBackend, S> Deref for PeekMut<'a, K, V, C, B, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.heap.slots[self.slot].value
+    }
+}
+
+impl<'a, K, V, C, B: HeapBackend, S> DerefMut for PeekMut<'a, K, V, C, B, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        &mut self.heap.slots[self.slot].value
+    }
+}
@@ -0,0 +1,501 @@
+use std::borrow::Borrow;
+use std::collections::{hash_map, HashMap};
+use std::fmt::{self, Debug, Display};
+use std::hash::Hash;
+use std::{error, iter, mem, slice, vec};
+
+use crate::clear::Clear;
+use crate::drain::Drain;
+use crate::entry_ref::EntryRef;
+use crate::{GenericMap, Lookup};
+
+pub use self::entry::{Entry, OccupEntry, VacEntry};
+
+mod entry;
+
+pub trait RadixKey: Copy + Ord + Hash + Debug + Default {
+    const BITS: u32;
+
+    fn bucket(self, floor: Self) -> usize;
+}
+
+macro_rules! radix_key {
+    ($($t:ty),*) => {
+        $(
+            impl RadixKey for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                fn bucket(self, floor: Self) -> usize {
+                    (Self::BITS - (self ^ floor).leading_zeros()) as usize
+                }
+            }
+        )*
+    };
+}
+
+radix_key!(u8, u16, u32, u64, u128, usize);
+
+pub struct HashedRadixHeap<K, V> {
+    map: HashMap<K, Position>,
+    buckets: Vec<Vec<(K, V)>>,
+    floor: K,
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+    bucket: usize,
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct MonotonicityError<K, V> {
+    pub key: K,
+    pub value: V,
+    pub floor: K,
+}
+
+impl<K: Debug, V> Display for MonotonicityError<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "key {:?} is below the last popped minimum {:?}",
+            self.key, self.floor
+        )
+    }
+}
+
+impl<K: Debug, V: Debug> error::Error for MonotonicityError<K, V> {}
+
+impl<K: RadixKey, V> HashedRadixHeap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            buckets: iter::repeat_with(Vec::new)
+                .take(K::BITS as usize + 1)
+                .collect(),
+            floor: K::default(),
+        }
+    }
+
+    pub fn floor(&self) -> K {
+        self.floor
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains_key<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn get<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let position = *self.map.get(key)?;
+        Some(&self.at(position).1)
+    }
+
+    pub fn get_key_value<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let position = *self.map.get(key)?;
+        let (k, v) = self.at(position);
+        Some((k, v))
+    }
+
+    pub fn get_mut<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        let position = *self.map.get(key)?;
+        Some(&mut self.at_mut(position).1)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.try_insert(key, value)
+            .unwrap_or_else(|err| panic!("HashedRadixHeap::insert: {}", err))
+    }
+
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, MonotonicityError<K, V>> {
+        match self.map.entry(key) {
+            hash_map::Entry::Occupied(occ) => {
+                let position = *occ.get();
+                Ok(Some(mem::replace(&mut self.at_mut(position).1, value)))
+            }
+            hash_map::Entry::Vacant(vac) => {
+                if key < self.floor {
+                    return Err(MonotonicityError {
+                        key,
+                        value,
+                        floor: self.floor,
+                    });
+                }
+                vac.insert(push(&mut self.buckets, self.floor, key, value));
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn remove<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        let position = self.map.remove(key)?;
+        Some(self.take(position))
+    }
+
+    pub fn drain(&mut self) -> DrainIter<'_, K, V> {
+        self.map.clear();
+        self.floor = K::default();
+        DrainIter {
+            bucket: None,
+            rest: self.buckets.iter_mut(),
+        }
+    }
+
+    pub fn entry(&mut self, key: K) -> crate::Entry<VacEntry<'_, K, V>, OccupEntry<'_, K, V>> {
+        match self.map.get(&key) {
+            Some(&position) => crate::Entry::Occupied(Entry::occupied(self, position)),
+            None => crate::Entry::Vacant(Entry::vacant(self, key)),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.buckets.iter().flatten().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.buckets.iter_mut().flatten().map(|(k, v)| (&*k, v))
+    }
+
+    pub fn min_key(&self) -> Option<&K> {
+        self.peek().map(|(k, _)| k)
+    }
+
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        let bucket = self.buckets.iter().find(|bucket| !bucket.is_empty())?;
+        bucket.iter().min_by_key(|(k, _)| k).map(|(k, v)| (k, v))
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        if self.buckets[0].is_empty() {
+            let b = self.buckets.iter().position(|bucket| !bucket.is_empty())?;
+            let mut bucket = mem::take(&mut self.buckets[b]);
+            self.floor = bucket.iter().map(|&(k, _)| k).min().unwrap();
+            for (key, value) in bucket.drain(..) {
+                let position = push(&mut self.buckets, self.floor, key, value);
+                *self.map.get_mut(&key).unwrap() = position;
+            }
+            self.buckets[b] = bucket;
+        }
+        let (key, value) = self.buckets[0].pop()?;
+        self.map.remove(&key);
+        Some((key, value))
+    }
+
+    fn at(&self, position: Position) -> &(K, V) {
+        &self.buckets[position.bucket][position.index]
+    }
+
+    fn at_mut(&mut self, position: Position) -> &mut (K, V) {
+        &mut self.buckets[position.bucket][position.index]
+    }
+
+    fn take(&mut self, position: Position) -> (K, V) {
+        let bucket = &mut self.buckets[position.bucket];
+        let result = bucket.swap_remove(position.index);
+        if let Some(&(moved, _)) = bucket.get(position.index) {
+            *self.map.get_mut(&moved).unwrap() = position;
+        }
+        result
+    }
+}
+
+fn push<K: RadixKey, V>(buckets: &mut [Vec<(K, V)>], floor: K, key: K, value: V) -> Position {
+    let bucket = key.bucket(floor);
+    buckets[bucket].push((key, value));
+    Position {
+        bucket,
+        index: buckets[bucket].len() - 1,
+    }
+}
+
+impl<K: RadixKey, V> Default for HashedRadixHeap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: RadixKey, V> Clear for HashedRadixHeap<K, V> {
+    fn clear(&mut self) {
+        self.map.clear();
+        for bucket in self.buckets.iter_mut() {
+            bucket.clear();
+        }
+        self.floor = K::default();
+    }
+}
+
+impl<K: RadixKey, V> Drain for HashedRadixHeap<K, V> {
+    type Output<'a> = DrainIter<'a, K, V>
+    where
+        Self: 'a;
+
+    fn drain(&mut self) -> Self::Output<'_> {
+        self.drain()
+    }
+}
+
+impl<K, V> IntoIterator for HashedRadixHeap<K, V> {
+    type Item = (K, V);
+    type IntoIter = iter::Flatten<vec::IntoIter<Vec<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.buckets.into_iter().flatten()
+    }
+}
+
+impl<K: RadixKey, V> Extend<(K, V)> for HashedRadixHeap<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: RadixKey, V> FromIterator<(K, V)> for HashedRadixHeap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut result = Self::new();
+        result.extend(iter);
+        result
+    }
+}
+
+pub type Iter<'a, K, V> =
+    iter::Map<iter::Flatten<slice::Iter<'a, Vec<(K, V)>>>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+
+pub type IterMut<'a, K, V> = iter::Map<
+    iter::Flatten<slice::IterMut<'a, Vec<(K, V)>>>,
+    fn(&'a mut (K, V)) -> (&'a K, &'a mut V),
+>;
+
+pub struct DrainIter<'a, K, V> {
+    bucket: Option<vec::Drain<'a, (K, V)>>,
+    rest: slice::IterMut<'a, Vec<(K, V)>>,
+}
+
+impl<'a, K, V> Iterator for DrainIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.bucket.as_mut().and_then(Iterator::next) {
+                return Some(entry);
+            }
+            self.bucket = Some(self.rest.next()?.drain(..));
+        }
+    }
+}
+
+impl<'a, K, V> Drop for DrainIter<'a, K, V> {
+    fn drop(&mut self) {
+        for bucket in &mut self.rest {
+            bucket.clear();
+        }
+    }
+}
+
+impl<K: RadixKey, V> GenericMap for HashedRadixHeap<K, V> {
+    type K = K;
+    type V = V;
+    type Iter<'a> = Iter<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        Self: 'a;
+
+    type IterMut<'a> = IterMut<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        Self: 'a;
+
+    type DrainIter<'a> = DrainIter<'a, K, V>
+    where
+        Self: 'a;
+
+    type VacEntry<'a> = VacEntry<'a, K, V>
+    where
+        Self: 'a;
+
+    type OccupEntry<'a> = OccupEntry<'a, K, V>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn drain(&mut self) -> Self::DrainIter<'_> {
+        self.drain()
+    }
+
+    fn entry(&mut self, key: K) -> crate::Entry<Self::VacEntry<'_>, Self::OccupEntry<'_>> {
+        self.entry(key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        self.iter_mut()
+    }
+}
+
+impl<K: RadixKey + Borrow<Q>, Q: ?Sized + Eq + Hash, V> Lookup<Q> for HashedRadixHeap<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
+    }
+}
+
+impl<K: RadixKey, V> EntryRef<K> for HashedRadixHeap<K, V> {
+    type VacEntryRef<'a> = VacEntry<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a;
+
+    type OccupEntryRef<'a> = OccupEntry<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a K,
+    ) -> crate::Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        self.entry(*key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monotone_inserts_between_pops_come_out_in_order() {
+        let mut heap = HashedRadixHeap::new();
+        for key in [40u32, 3, 17] {
+            heap.insert(key, ());
+        }
+        assert_eq!(heap.pop_min(), Some((3, ())));
+        heap.insert(3, ());
+        heap.insert(12, ());
+        assert_eq!(heap.pop_min(), Some((3, ())));
+        assert_eq!(heap.pop_min(), Some((12, ())));
+        heap.insert(16, ());
+        let rest: Vec<_> = iter::from_fn(|| heap.pop_min()).map(|(k, _)| k).collect();
+        assert_eq!(rest, [16, 17, 40]);
+        assert_eq!(heap.floor(), 40);
+    }
+
+    #[test]
+    fn keys_below_the_floor_are_rejected() {
+        let mut heap = HashedRadixHeap::new();
+        heap.insert(10u8, 'a');
+        heap.insert(20, 'b');
+        assert_eq!(heap.pop_min(), Some((10, 'a')));
+        let err = heap.try_insert(9, 'c').unwrap_err();
+        assert_eq!((err.key, err.value, err.floor), (9, 'c', 10));
+        assert_eq!(err.to_string(), "key 9 is below the last popped minimum 10");
+        assert_eq!(heap.try_insert(20, 'd').unwrap(), Some('b'));
+        assert_eq!(heap.len(), 1);
+    }
+
+    #[test]
+    fn removing_from_a_bucket_keeps_positions_valid() {
+        let mut heap = HashedRadixHeap::new();
+        for key in [8u64, 9, 10, 11, 12] {
+            heap.insert(key, key * 2);
+        }
+        assert_eq!(heap.remove(&9), Some(18));
+        assert_eq!(heap.get(&11), Some(&22));
+        *heap.get_mut(&12).unwrap() = 0;
+        assert_eq!(heap.remove(&8), Some(16));
+        assert_eq!(heap.peek(), Some((&10, &20)));
+        let rest: Vec<_> = iter::from_fn(|| heap.pop_min()).collect();
+        assert_eq!(rest, [(10, 20), (11, 22), (12, 0)]);
+    }
+
+    #[test]
+    fn extreme_keys_use_the_top_bucket() {
+        let mut heap = HashedRadixHeap::new();
+        heap.insert(u64::MAX, ());
+        heap.insert(0, ());
+        assert_eq!(heap.pop_min(), Some((0, ())));
+        assert_eq!(heap.pop_min(), Some((u64::MAX, ())));
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn partial_drain_empties_every_bucket() {
+        let mut heap = HashedRadixHeap::new();
+        for key in [1u32, 2, 100, 1000] {
+            heap.insert(key, ());
+        }
+        assert!(heap.drain().next().is_some());
+        assert!(heap.is_empty());
+        assert_eq!(heap.iter().count(), 0);
+        heap.insert(1000, ());
+        assert_eq!(heap.pop_min(), Some((1000, ())));
+        assert_eq!(heap.pop_min(), None);
+    }
+}
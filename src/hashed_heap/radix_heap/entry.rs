@@ -0,0 +1,141 @@
+use std::mem;
+
+use crate::{OccupiedEntry, VacantEntry};
+
+use super::{push, HashedRadixHeap, Position, RadixKey};
+
+pub struct Entry<'a, K, V, E> {
+    heap: &'a mut HashedRadixHeap<K, V>,
+    entry: E,
+}
+
+pub struct Vacant<K> {
+    key: K,
+}
+
+pub struct Occupied {
+    position: Position,
+}
+
+impl<'a, K, V> VacEntry<'a, K, V> {
+    pub(super) fn vacant(heap: &'a mut HashedRadixHeap<K, V>, key: K) -> Self {
+        Self {
+            heap,
+            entry: Vacant { key },
+        }
+    }
+}
+
+impl<'a, K, V> OccupEntry<'a, K, V> {
+    pub(super) fn occupied(heap: &'a mut HashedRadixHeap<K, V>, position: Position) -> Self {
+        Self {
+            heap,
+            entry: Occupied { position },
+        }
+    }
+}
+
+pub type VacEntry<'a, K, V> = Entry<'a, K, V, Vacant<K>>;
+pub type OccupEntry<'a, K, V> = Entry<'a, K, V, Occupied>;
+
+impl<'a, K: RadixKey, V> VacEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.entry.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V> {
+        let Vacant { key } = self.entry;
+        let heap = self.heap;
+        assert!(
+            key >= heap.floor,
+            "HashedRadixHeap::insert: key {:?} is below the last popped minimum {:?}",
+            key,
+            heap.floor
+        );
+        let position = push(&mut heap.buckets, heap.floor, key, value);
+        heap.map.insert(key, position);
+        Entry::occupied(heap, position)
+    }
+}
+
+impl<'a, K: RadixKey, V> VacantEntry<'a, K, V> for VacEntry<'a, K, V> {
+    type Occupied = OccupEntry<'a, K, V>;
+
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(self, value: V) -> &'a mut V {
+        self.insert(value)
+    }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
+}
+
+impl<'a, K: RadixKey, V> OccupEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.heap.at(self.entry.position).0
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let (key, value) = self.heap.take(self.entry.position);
+        self.heap.map.remove(&key);
+        (key, value)
+    }
+
+    pub fn get(&self) -> &V {
+        &self.heap.at(self.entry.position).1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.heap.at_mut(self.entry.position).1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.heap.at_mut(self.entry.position).1
+    }
+}
+
+impl<'a, K: RadixKey, V> OccupiedEntry<'a, K, V> for OccupEntry<'a, K, V> {
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(&mut self, value: V) -> V {
+        self.insert(value)
+    }
+
+    fn remove(self) -> V {
+        self.remove()
+    }
+
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
+    fn get(&self) -> &V {
+        self.get()
+    }
+
+    fn get_mut(&mut self) -> &mut V {
+        self.get_mut()
+    }
+
+    fn into_mut(self) -> &'a mut V {
+        self.into_mut()
+    }
+}
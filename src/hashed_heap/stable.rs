@@ -0,0 +1,653 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::vec;
+
+use crate::clear::Clear;
+use crate::drain::Drain;
+use crate::entry_ref::{EntryRef, KeySource, PendingKey};
+use crate::{GenericMap, Lookup, OccupiedEntry, VacantEntry};
+
+use super::backend::{Binary, HeapBackend};
+use super::comparator::Comparator;
+use super::entry::Vacant;
+use super::HashedHeap;
+
+pub struct StableHashedHeap<K, V, C, B: HeapBackend = Binary> {
+    heap: HashedHeap<Sequenced<K>, V, Fifo<C>, B>,
+    next_seq: u64,
+}
+
+struct Sequenced<K> {
+    key: K,
+    seq: u64,
+}
+
+impl<K: PartialEq> PartialEq for Sequenced<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Eq> Eq for Sequenced<K> {}
+
+impl<K: Hash> Hash for Sequenced<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+struct Fifo<C>(C);
+
+impl<K, C: Comparator<K>> Comparator<Sequenced<K>> for Fifo<C> {
+    fn favors(&self, a: &Sequenced<K>, b: &Sequenced<K>) -> bool {
+        self.0.favors(&a.key, &b.key) || !self.0.favors(&b.key, &a.key) && a.seq < b.seq
+    }
+}
+
+impl<K, V, C, B: HeapBackend> StableHashedHeap<K, V, C, B> {
+    pub fn new() -> Self
+    where
+        C: Default,
+    {
+        Self::with_comparator(C::default())
+    }
+
+    pub fn with_comparator(compare: C) -> Self {
+        Self {
+            heap: HashedHeap::with_comparator(Fifo(compare)),
+            next_seq: 0,
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.heap.comparator().0
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn contains_key<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<&V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        Some(&self.heap.slots[slot].value)
+    }
+
+    pub fn get_key_value<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        Some((&self.heap.key_at(slot).key, &self.heap.slots[slot].value))
+    }
+
+    pub fn get_mut<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        Some(&mut self.heap.slots[slot].value)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        C: Comparator<K>,
+    {
+        match self.entry(key) {
+            crate::Entry::Vacant(vac) => {
+                vac.insert(value);
+                None
+            }
+            crate::Entry::Occupied(mut occ) => Some(occ.insert(value)),
+        }
+    }
+
+    pub fn remove<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<K>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<K>,
+    {
+        let slot = self.find(key)?;
+        self.heap.unlink(slot);
+        let (key, value) = self.heap.remove_slot(slot);
+        Some((key.key, value))
+    }
+
+    pub fn drain(&mut self) -> IntoIter<K, V> {
+        self.next_seq = 0;
+        IntoIter::in_sequence(self.heap.drain().collect())
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn entry(
+        &mut self,
+        key: K,
+    ) -> crate::Entry<VacEntry<'_, K, V, C, B>, OccupEntry<'_, K, V, C, B>>
+    where
+        K: Eq + Hash,
+    {
+        match self.heap.entry(Sequenced { key, seq: 0 }) {
+            crate::Entry::Vacant(vac) => {
+                crate::Entry::Vacant(VacEntry(vac.map_key(|key| key.key), &mut self.next_seq))
+            }
+            crate::Entry::Occupied(occ) => crate::Entry::Occupied(OccupEntry(occ)),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.heap.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut(self.heap.iter_mut())
+    }
+
+    pub fn top_key(&self) -> Option<&K> {
+        self.heap.top_key().map(|k| &k.key)
+    }
+
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        self.heap.peek().map(|(k, v)| (&k.key, v))
+    }
+
+    pub fn pop_top(&mut self) -> Option<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        self.heap.pop_top().map(|(k, v)| (k.key, v))
+    }
+
+    pub fn iter_sorted(&self) -> SortedIter<'_, K, V, C, B>
+    where
+        C: Comparator<K>,
+    {
+        SortedIter(self.heap.iter_sorted())
+    }
+
+    fn find<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<usize>
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        let hash = self.heap.hasher.hash_one(key);
+        self.heap.find_with(hash, |k| k.key.borrow() == key)
+    }
+}
+
+impl<K, V, C: Default, B: HeapBackend> Default for StableHashedHeap<K, V, C, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, C, B: HeapBackend> Clear for StableHashedHeap<K, V, C, B> {
+    fn clear(&mut self) {
+        self.heap.clear();
+        self.next_seq = 0;
+    }
+}
+
+impl<K, V, C, B: HeapBackend> Drain for StableHashedHeap<K, V, C, B> {
+    type Output<'a> = IntoIter<K, V>
+    where
+        Self: 'a;
+
+    fn drain(&mut self) -> Self::Output<'_> {
+        self.drain()
+    }
+}
+
+impl<K, V, C, B: HeapBackend> IntoIterator for StableHashedHeap<K, V, C, B> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::in_sequence(self.heap.into_iter().collect())
+    }
+}
+
+impl<K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend> Extend<(K, V)>
+    for StableHashedHeap<K, V, C, B>
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let next_seq = &mut self.next_seq;
+        self.heap.extend(iter.into_iter().map(|(key, value)| {
+            let seq = *next_seq;
+            *next_seq += 1;
+            (Sequenced { key, seq }, value)
+        }));
+    }
+}
+
+impl<K: Eq + Hash, V, C: Comparator<K> + Default, B: HeapBackend> FromIterator<(K, V)>
+    for StableHashedHeap<K, V, C, B>
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut result = Self::new();
+        result.extend(iter);
+        result
+    }
+}
+
+pub struct VacEntry<'a, K, V, C, B: HeapBackend = Binary, T = K>(
+    super::Entry<'a, Sequenced<K>, V, Fifo<C>, Vacant<T>, B>,
+    &'a mut u64,
+);
+
+pub type VacEntryRef<'a, K, V, C, Q, B = Binary> = VacEntry<'a, K, V, C, B, PendingKey<'a, K, Q>>;
+
+impl<'a, K, V, C, B: HeapBackend, T: KeySource<K>> VacEntry<'a, K, V, C, B, T> {
+    pub fn key(&self) -> &K {
+        self.0.source().key()
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V
+    where
+        C: Comparator<K>,
+    {
+        self.sequenced().insert(value)
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V, C, B>
+    where
+        C: Comparator<K>,
+    {
+        OccupEntry(self.sequenced().insert_entry(value))
+    }
+
+    fn sequenced(self) -> super::VacEntry<'a, Sequenced<K>, V, Fifo<C>, B> {
+        let Self(entry, next_seq) = self;
+        let seq = *next_seq;
+        *next_seq += 1;
+        entry.map_key(|key| Sequenced {
+            key: key.into_key(),
+            seq,
+        })
+    }
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend, T: KeySource<K>> VacantEntry<'a, K, V>
+    for VacEntry<'a, K, V, C, B, T>
+{
+    type Occupied = OccupEntry<'a, K, V, C, B>;
+
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(self, value: V) -> &'a mut V {
+        self.insert(value)
+    }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
+}
+
+pub struct OccupEntry<'a, K, V, C, B: HeapBackend = Binary>(
+    super::OccupEntry<'a, Sequenced<K>, V, Fifo<C>, B>,
+);
+
+impl<'a, K, V, C, B: HeapBackend> OccupEntry<'a, K, V, C, B> {
+    pub fn key(&self) -> &K {
+        &self.0.key().key
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        self.0.insert(value)
+    }
+
+    pub fn remove(self) -> V
+    where
+        C: Comparator<K>,
+    {
+        self.0.remove()
+    }
+
+    pub fn remove_entry(self) -> (K, V)
+    where
+        C: Comparator<K>,
+    {
+        let (key, value) = self.0.remove_entry();
+        (key.key, value)
+    }
+
+    pub fn replace_entry(self, key: K, value: V) -> (K, V) {
+        let seq = self.0.key().seq;
+        let (key, value) = self.0.replace_entry(Sequenced { key, seq }, value);
+        (key.key, value)
+    }
+
+    pub fn get(&self) -> &V {
+        self.0.get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.0.get_mut()
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.0.into_mut()
+    }
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend> OccupiedEntry<'a, K, V>
+    for OccupEntry<'a, K, V, C, B>
+{
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(&mut self, value: V) -> V {
+        self.insert(value)
+    }
+
+    fn remove(self) -> V {
+        self.remove()
+    }
+
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
+    fn replace_entry(self, key: K, value: V) -> (K, V) {
+        self.replace_entry(key, value)
+    }
+
+    fn get(&self) -> &V {
+        self.get()
+    }
+
+    fn get_mut(&mut self) -> &mut V {
+        self.get_mut()
+    }
+
+    fn into_mut(self) -> &'a mut V {
+        self.into_mut()
+    }
+}
+
+pub struct Iter<'a, K, V>(super::Iter<'a, Sequenced<K>, V>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (&k.key, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+pub struct IterMut<'a, K, V>(super::IterMut<'a, Sequenced<K>, V>);
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (&k.key, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+pub struct SortedIter<'a, K, V, C, B: HeapBackend = Binary>(
+    super::SortedIter<'a, Sequenced<K>, V, Fifo<C>, B>,
+);
+
+impl<'a, K, V, C: Comparator<K>, B: HeapBackend> Iterator for SortedIter<'a, K, V, C, B> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (&k.key, v))
+    }
+}
+
+pub struct IntoIter<K, V>(vec::IntoIter<(Sequenced<K>, V)>);
+
+impl<K, V> IntoIter<K, V> {
+    fn in_sequence(mut entries: Vec<(Sequenced<K>, V)>) -> Self {
+        entries.sort_unstable_by_key(|(k, _)| k.seq);
+        Self(entries.into_iter())
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k.key, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K: Eq + Hash, V, C: Comparator<K>, B: HeapBackend> GenericMap
+    for StableHashedHeap<K, V, C, B>
+{
+    type K = K;
+    type V = V;
+    type Iter<'a> = Iter<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        Self: 'a;
+
+    type IterMut<'a> = IterMut<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        Self: 'a;
+
+    type DrainIter<'a> = IntoIter<K, V>
+    where
+        Self: 'a;
+
+    type VacEntry<'a> = VacEntry<'a, K, V, C, B>
+    where
+        Self: 'a;
+
+    type OccupEntry<'a> = OccupEntry<'a, K, V, C, B>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn drain(&mut self) -> Self::DrainIter<'_> {
+        self.drain()
+    }
+
+    fn entry(&mut self, key: K) -> crate::Entry<Self::VacEntry<'_>, Self::OccupEntry<'_>> {
+        self.entry(key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        self.iter_mut()
+    }
+}
+
+impl<K, Q, V, C, B> Lookup<Q> for StableHashedHeap<K, V, C, B>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: ?Sized + Eq + Hash,
+    C: Comparator<K>,
+    B: HeapBackend,
+{
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
+    }
+}
+
+impl<K, Q, V, C, B> EntryRef<Q> for StableHashedHeap<K, V, C, B>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: ?Sized + Eq + Hash + ToOwned<Owned = K>,
+    C: Comparator<K>,
+    B: HeapBackend,
+{
+    type VacEntryRef<'a> = VacEntryRef<'a, K, V, C, Q, B>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    type OccupEntryRef<'a> = OccupEntry<'a, K, V, C, B>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> crate::Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        let hash = self.heap.hasher.hash_one(key);
+        match self.heap.find_with(hash, |k| k.key.borrow() == key) {
+            Some(slot) => {
+                crate::Entry::Occupied(OccupEntry(super::Entry::occupied(&mut self.heap, slot)))
+            }
+            None => {
+                let entry = super::Entry::vacant(&mut self.heap, PendingKey::new(key), hash);
+                crate::Entry::Vacant(VacEntry(entry, &mut self.next_seq))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use super::super::comparator::Min;
+    use super::*;
+
+    #[derive(Default)]
+    struct ByRank;
+
+    impl Comparator<(u32, char)> for ByRank {
+        fn favors(&self, a: &(u32, char), b: &(u32, char)) -> bool {
+            a.0 < b.0
+        }
+    }
+
+    fn filled() -> StableHashedHeap<(u32, char), (), ByRank> {
+        let mut heap = StableHashedHeap::new();
+        for key in [(2, 'a'), (1, 'b'), (2, 'c'), (1, 'd'), (2, 'e')] {
+            heap.insert(key, ());
+        }
+        heap
+    }
+
+    fn pop_names(heap: &mut StableHashedHeap<(u32, char), (), ByRank>) -> String {
+        iter::from_fn(|| heap.pop_top())
+            .map(|((_, name), _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn ties_pop_in_insertion_order() {
+        let mut heap = filled();
+        let sorted: String = heap.iter_sorted().map(|((_, name), _)| name).collect();
+        assert_eq!(sorted, "bdace");
+        assert_eq!(pop_names(&mut heap), "bdace");
+    }
+
+    #[test]
+    fn removing_a_tied_entry_keeps_the_others_in_order() {
+        let mut heap = filled();
+        assert_eq!(heap.remove(&(2, 'c')), Some(()));
+        assert_eq!(heap.remove(&(1, 'b')), Some(()));
+        assert_eq!(heap.remove(&(1, 'b')), None);
+        heap.insert((1, 'f'), ());
+        assert_eq!(pop_names(&mut heap), "dfae");
+    }
+
+    #[test]
+    fn drain_restarts_the_sequence() {
+        let mut heap = filled();
+        let drained: String = heap.drain().map(|((_, name), _)| name).collect();
+        assert_eq!(drained, "abcde");
+        assert!(heap.is_empty());
+        heap.insert((3, 'y'), ());
+        heap.insert((3, 'x'), ());
+        assert_eq!(pop_names(&mut heap), "yx");
+    }
+
+    #[test]
+    fn borrowed_keys_find_sequenced_entries() {
+        let mut heap = StableHashedHeap::<String, u32, Min<String>>::new();
+        heap.insert("b".to_owned(), 2);
+        heap.insert("a".to_owned(), 1);
+        assert!(Lookup::contains_key(&heap, "a"));
+        *Lookup::get_mut(&mut heap, "b").unwrap() += 10;
+        assert_eq!(heap.get_key_value("b"), Some((&"b".to_owned(), &12)));
+        assert_eq!(
+            Lookup::remove_entry(&mut heap, "a"),
+            Some(("a".to_owned(), 1))
+        );
+        assert_eq!(heap.remove("a"), None);
+        assert_eq!(heap.len(), 1);
+    }
+}
@@ -0,0 +1,575 @@
+use std::borrow::Borrow;
+use std::collections::{hash_map, HashMap};
+use std::hash::Hash;
+use std::{iter, mem, slice, vec};
+
+use crate::clear::Clear;
+use crate::drain::Drain;
+use crate::entry_ref::{EntryRef, PendingKey};
+use crate::{GenericMap, Lookup};
+
+use super::comparator::{Comparator, Max, Min};
+use super::indexed_heap::{Index, IndexedHeap};
+
+pub use self::entry::{Entry, OccupEntry, OccupEntryRef, VacEntry, VacEntryRef};
+
+use self::entry::Occupied;
+pub use self::priority_mut::PriorityMut;
+
+mod entry;
+mod priority_mut;
+
+pub struct HashedValueHeap<K, V, C> {
+    map: HashMap<K, Index>,
+    heap: PriorityHeap<K, V, C>,
+}
+
+pub type HashedValueMaxHeap<K, V> = HashedValueHeap<K, V, Max<V>>;
+pub type HashedValueMinHeap<K, V> = HashedValueHeap<K, V, Min<V>>;
+
+struct ByValue<C>(C);
+
+impl<K, V, C: Comparator<V>> Comparator<(K, V)> for ByValue<C> {
+    fn favors(&self, a: &(K, V), b: &(K, V)) -> bool {
+        self.0.favors(&a.1, &b.1)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Pending {
+    Clean,
+    One(Index),
+    All,
+}
+
+struct PriorityHeap<K, V, C> {
+    heap: IndexedHeap<(K, V), ByValue<C>>,
+    pending: Pending,
+}
+
+impl<K, V, C> PriorityHeap<K, V, C> {
+    fn with_comparator(compare: C) -> Self {
+        Self {
+            heap: IndexedHeap::with_comparator(ByValue(compare)),
+            pending: Pending::Clean,
+        }
+    }
+
+    fn settle(&mut self, map: &mut HashMap<K, Index>)
+    where
+        K: Eq + Hash,
+        C: Comparator<V>,
+    {
+        match mem::replace(&mut self.pending, Pending::Clean) {
+            Pending::Clean => {}
+            Pending::One(index) => reindex(map, self.heap.update(index)),
+            Pending::All => reindex(map, self.heap.rebuild()),
+        }
+    }
+
+    fn peek(&self) -> Option<&(K, V)>
+    where
+        C: Comparator<V>,
+    {
+        match self.pending {
+            Pending::Clean => self.heap.peek(),
+            Pending::One(_) | Pending::All => {
+                let compare = self.heap.comparator();
+                self.heap.iter().reduce(|a, b| compare.favored(a, b))
+            }
+        }
+    }
+}
+
+fn reindex<'a, K: Eq + Hash + 'a, V: 'a>(
+    map: &mut HashMap<K, Index>,
+    changes: impl Iterator<Item = (Index, &'a (K, V))>,
+) {
+    for (new_index, (k, _)) in changes {
+        *map.get_mut(k).unwrap() = new_index;
+    }
+}
+
+impl<K, V> HashedValueMaxHeap<K, V> {
+    pub fn pop_max(&mut self) -> Option<(K, V)>
+    where
+        K: Eq + Hash,
+        V: PartialOrd,
+    {
+        self.pop_top()
+    }
+}
+
+impl<K, V> HashedValueMinHeap<K, V> {
+    pub fn pop_min(&mut self) -> Option<(K, V)>
+    where
+        K: Eq + Hash,
+        V: PartialOrd,
+    {
+        self.pop_top()
+    }
+}
+
+impl<K, V, C> HashedValueHeap<K, V, C> {
+    pub fn new() -> Self
+    where
+        C: Default,
+    {
+        Self::with_comparator(C::default())
+    }
+
+    pub fn with_comparator(compare: C) -> Self {
+        Self {
+            map: HashMap::new(),
+            heap: PriorityHeap::with_comparator(compare),
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.heap.heap.comparator().0
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains_key<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn get<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<&V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        let index = *self.map.get(key)?;
+        Some(&self.heap.heap[index].1)
+    }
+
+    pub fn get_key_value<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Eq + Hash + Borrow<Q>,
+    {
+        let index = *self.map.get(key)?;
+        let (k, v) = &self.heap.heap[index];
+        Some((k, v))
+    }
+
+    pub fn get_mut<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<PriorityMut<'_, K, V, C>>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        let index = *self.map.get(key)?;
+        let map = &mut self.map as *mut HashMap<K, Index>;
+        Some(unsafe { PriorityMut::new(map, &mut self.heap, index) })
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Eq + Hash + Clone,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        match self.map.entry(key) {
+            hash_map::Entry::Occupied(occ) => {
+                let index = *occ.get();
+                let result = mem::replace(&mut self.heap.heap[index].1, value);
+                reindex(&mut self.map, self.heap.heap.update(index));
+                Some(result)
+            }
+            hash_map::Entry::Vacant(vac) => {
+                let (index, changes) = self.heap.heap.insert((vac.key().clone(), value));
+                vac.insert(index);
+                reindex(&mut self.map, changes);
+                None
+            }
+        }
+    }
+
+    pub fn change_priority<Q: ?Sized + Eq + Hash>(&mut self, key: &Q, value: V) -> Option<V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        let index = *self.map.get(key)?;
+        let result = mem::replace(&mut self.heap.heap[index].1, value);
+        reindex(&mut self.map, self.heap.heap.update(index));
+        Some(result)
+    }
+
+    pub fn decrease_key<Q: ?Sized + Eq + Hash>(&mut self, key: &Q, value: V) -> bool
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        let Some(&index) = self.map.get(key) else {
+            return false;
+        };
+        if !self.comparator().favors(&value, &self.heap.heap[index].1) {
+            return false;
+        }
+        self.heap.heap[index].1 = value;
+        reindex(&mut self.map, self.heap.heap.update(index));
+        true
+    }
+
+    pub fn remove<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<V>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        let index = self.map.remove(key)?;
+        let ((k, result), changes) = self.heap.heap.remove(index);
+        reindex(&mut self.map, changes);
+        Some((k, result))
+    }
+
+    pub fn drain(&mut self) -> DrainIter<'_, K, V> {
+        self.map.clear();
+        self.heap.pending = Pending::Clean;
+        self.heap.heap.drain()
+    }
+
+    pub fn entry(&mut self, key: K) -> crate::Entry<VacEntry<'_, K, V, C>, OccupEntry<'_, K, V, C>>
+    where
+        K: Eq + Hash,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        if self.map.contains_key(&key) {
+            let map = &mut self.map as *mut HashMap<K, Index>;
+            return crate::Entry::Occupied(unsafe {
+                Entry::new(map, &mut self.heap, Occupied::new(key))
+            });
+        }
+        let map = &mut self.map as *mut HashMap<K, Index>;
+        match unsafe { (*map).entry(key) } {
+            hash_map::Entry::Vacant(vac) => {
+                crate::Entry::Vacant(unsafe { Entry::new(map, &mut self.heap, vac) })
+            }
+            hash_map::Entry::Occupied(_) => unreachable!(),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.heap.heap.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.heap.pending = Pending::All;
+        self.heap.heap.iter_mut().map(|(k, v)| (&*k, v))
+    }
+
+    pub fn top_key(&self) -> Option<&K>
+    where
+        C: Comparator<V>,
+    {
+        self.heap.peek().map(|(k, _)| k)
+    }
+
+    pub fn peek(&self) -> Option<(&K, &V)>
+    where
+        C: Comparator<V>,
+    {
+        self.heap.peek().map(|(k, v)| (k, v))
+    }
+
+    pub fn pop_top(&mut self) -> Option<(K, V)>
+    where
+        K: Eq + Hash,
+        C: Comparator<V>,
+    {
+        self.heap.settle(&mut self.map);
+        self.heap.heap.peek()?;
+        let ((key, value), changes) = self.heap.heap.remove(Index::TOP);
+        reindex(&mut self.map, changes);
+        self.map.remove(&key);
+        Some((key, value))
+    }
+}
+
+impl<K, V, C: Default> Default for HashedValueHeap<K, V, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, C> Clear for HashedValueHeap<K, V, C> {
+    fn clear(&mut self) {
+        self.map.clear();
+        self.heap.heap.clear();
+        self.heap.pending = Pending::Clean;
+    }
+}
+
+impl<K, V, C> Drain for HashedValueHeap<K, V, C> {
+    type Output<'a> = DrainIter<'a, K, V>
+    where
+        Self: 'a;
+
+    fn drain(&mut self) -> Self::Output<'_> {
+        self.drain()
+    }
+}
+
+impl<K, V, C> IntoIterator for HashedValueHeap<K, V, C> {
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.heap.heap.into_vec().into_iter()
+    }
+}
+
+impl<K: Eq + Hash + Clone, V, C: Comparator<V>> Extend<(K, V)> for HashedValueHeap<K, V, C> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+pub type Iter<'a, K, V> = iter::Map<slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+
+pub type IterMut<'a, K, V> =
+    iter::Map<slice::IterMut<'a, (K, V)>, fn(&'a mut (K, V)) -> (&'a K, &'a mut V)>;
+
+pub type DrainIter<'a, K, V> = vec::Drain<'a, (K, V)>;
+
+impl<K: Eq + Hash + Clone, V, C: Comparator<V>> GenericMap for HashedValueHeap<K, V, C> {
+    type K = K;
+    type V = V;
+    type Iter<'a> = Iter<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        Self: 'a;
+
+    type IterMut<'a> = IterMut<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        Self: 'a;
+
+    type DrainIter<'a> = DrainIter<'a, K, V>
+    where
+        Self: 'a;
+
+    type VacEntry<'a> = VacEntry<'a, K, V, C>
+    where
+        Self: 'a;
+
+    type OccupEntry<'a> = OccupEntry<'a, K, V, C>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn drain(&mut self) -> Self::DrainIter<'_> {
+        self.drain()
+    }
+
+    fn entry(&mut self, key: K) -> crate::Entry<Self::VacEntry<'_>, Self::OccupEntry<'_>> {
+        self.entry(key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        self.iter_mut()
+    }
+}
+
+impl<K, Q, V, C> Lookup<Q> for HashedValueHeap<K, V, C>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: ?Sized + Eq + Hash,
+    C: Comparator<V>,
+{
+    type Key = K;
+    type Value = V;
+
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key).map(PriorityMut::into_mut)
+    }
+
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
+    }
+}
+
+impl<K, Q, V, C> EntryRef<Q> for HashedValueHeap<K, V, C>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: ?Sized + Eq + Hash + ToOwned<Owned = K>,
+    C: Comparator<V>,
+{
+    type VacEntryRef<'a> = VacEntryRef<'a, K, V, C, Q>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    type OccupEntryRef<'a> = OccupEntryRef<'a, K, V, C, Q>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> crate::Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        self.heap.settle(&mut self.map);
+        let found = self.map.contains_key(key);
+        let map = &mut self.map as *mut HashMap<K, Index>;
+        if found {
+            crate::Entry::Occupied(unsafe { Entry::new(map, &mut self.heap, Occupied::new(key)) })
+        } else {
+            crate::Entry::Vacant(unsafe { Entry::new(map, &mut self.heap, PendingKey::new(key)) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain_keys<C: Comparator<u32>>(
+        heap: &mut HashedValueHeap<&'static str, u32, C>,
+    ) -> Vec<&'static str> {
+        iter::from_fn(|| heap.pop_top()).map(|(k, _)| k).collect()
+    }
+
+    fn filled() -> HashedValueMinHeap<&'static str, u32> {
+        let mut heap = HashedValueMinHeap::new();
+        heap.insert("a", 5);
+        heap.insert("b", 3);
+        heap.insert("c", 8);
+        heap
+    }
+
+    #[test]
+    fn orders_by_value_not_key() {
+        let mut heap = filled();
+        assert_eq!(heap.peek(), Some((&"b", &3)));
+        assert_eq!(heap.insert("a", 1), Some(5));
+        assert_eq!(drain_keys(&mut heap), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn change_priority_moves_in_both_directions() {
+        let mut heap = filled();
+        assert_eq!(heap.change_priority(&"c", 1), Some(8));
+        assert_eq!(heap.change_priority(&"b", 9), Some(3));
+        assert_eq!(heap.change_priority(&"d", 0), None);
+        assert_eq!(drain_keys(&mut heap), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn decrease_key_rejects_worse_and_equal_priorities() {
+        let mut heap = filled();
+        assert!(!heap.decrease_key(&"a", 6));
+        assert!(!heap.decrease_key(&"a", 5));
+        assert!(!heap.decrease_key(&"d", 0));
+        assert!(heap.decrease_key(&"c", 2));
+        assert_eq!(heap.pop_min(), Some(("c", 2)));
+    }
+
+    #[test]
+    fn get_mut_guard_resifts_on_drop() {
+        let mut heap = filled();
+        *heap.get_mut(&"c").unwrap() = 0;
+        assert_eq!(heap.top_key(), Some(&"c"));
+        *heap.get_mut(&"c").unwrap() = 10;
+        assert_eq!(heap.top_key(), Some(&"b"));
+        assert_eq!(drain_keys(&mut heap), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn entry_references_resift_lazily() {
+        let mut heap = filled();
+        match heap.entry("a") {
+            crate::Entry::Occupied(entry) => *PriorityMut::into_mut(entry.into_mut()) = 0,
+            crate::Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(heap.peek(), Some((&"a", &0)));
+        match heap.entry("d") {
+            crate::Entry::Vacant(entry) => *entry.insert(4) = 20,
+            crate::Entry::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(heap.remove(&"b"), Some(3));
+        assert_eq!(drain_keys(&mut heap), ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn occupied_entry_follows_its_value_after_resifts() {
+        let mut heap = filled();
+        let crate::Entry::Occupied(mut entry) = heap.entry("c") else {
+            unreachable!()
+        };
+        assert_eq!(entry.insert(1), 8);
+        *PriorityMut::into_mut(entry.get_mut()) = 9;
+        assert_eq!((entry.key(), entry.get()), (&"c", &9));
+        *entry.get_mut() = 2;
+        assert_eq!(entry.remove_entry(), ("c", 2));
+        assert_eq!(drain_keys(&mut heap), ["b", "a"]);
+    }
+
+    #[test]
+    fn borrowed_keys_reach_value_entries() {
+        let mut heap = HashedValueMinHeap::<String, u32>::new();
+        heap.insert("x".to_owned(), 5);
+        heap.insert("y".to_owned(), 3);
+        assert!(Lookup::contains_key(&heap, "x"));
+        assert_eq!(heap.change_priority("x", 1), Some(5));
+        assert_eq!(heap.get_key_value("x"), Some((&"x".to_owned(), &1)));
+        assert_eq!(Lookup::remove(&mut heap, "y"), Some(3));
+        assert_eq!(heap.pop_min(), Some(("x".to_owned(), 1)));
+    }
+}
@@ -0,0 +1,276 @@
+use std::borrow::Borrow;
+use std::collections::{hash_map, HashMap};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem;
+
+use crate::entry_ref::{KeySource, PendingKey};
+use crate::hashed_heap::comparator::Comparator;
+use crate::hashed_heap::indexed_heap::Index;
+use crate::{OccupiedEntry, VacantEntry};
+
+use super::{reindex, PriorityHeap, PriorityMut};
+
+pub struct Entry<'a, K, V, C, E> {
+    map: *mut HashMap<K, Index>,
+    heap: &'a mut PriorityHeap<K, V, C>,
+    entry: E,
+}
+
+impl<'a, K, V, C, E> Entry<'a, K, V, C, E> {
+    pub(super) unsafe fn new(
+        map: *mut HashMap<K, Index>,
+        heap: &'a mut PriorityHeap<K, V, C>,
+        entry: E,
+    ) -> Self {
+        Self { map, heap, entry }
+    }
+}
+
+pub struct Occupied<T, Q: ?Sized = T> {
+    key: T,
+    query: PhantomData<fn(&Q)>,
+}
+
+impl<T, Q: ?Sized> Occupied<T, Q> {
+    pub(super) fn new(key: T) -> Self {
+        Self {
+            key,
+            query: PhantomData,
+        }
+    }
+}
+
+pub type VacEntry<'a, K, V, C> = Entry<'a, K, V, C, hash_map::VacantEntry<'a, K, Index>>;
+pub type VacEntryRef<'a, K, V, C, Q> = Entry<'a, K, V, C, PendingKey<'a, K, Q>>;
+pub type OccupEntry<'a, K, V, C> = Entry<'a, K, V, C, Occupied<K>>;
+pub type OccupEntryRef<'a, K, V, C, Q> = Entry<'a, K, V, C, Occupied<&'a Q, Q>>;
+
+impl<'a, K, V, C> VacEntry<'a, K, V, C> {
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    pub fn insert(self, value: V) -> PriorityMut<'a, K, V, C>
+    where
+        K: Eq + Hash + Clone,
+        C: Comparator<V>,
+    {
+        let (index, changed_indices) = self.heap.heap.insert((self.entry.key().clone(), value));
+        self.entry.insert(index);
+        let map = unsafe { &mut *self.map };
+        reindex(map, changed_indices);
+        unsafe { PriorityMut::new(self.map, self.heap, index) }
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V, C>
+    where
+        K: Eq + Hash + Clone,
+        C: Comparator<V>,
+    {
+        let key = self.entry.key().clone();
+        let (index, changed_indices) = self.heap.heap.insert((key.clone(), value));
+        self.entry.insert(index);
+        let map = unsafe { &mut *self.map };
+        reindex(map, changed_indices);
+        unsafe { Entry::new(self.map, self.heap, Occupied::new(key)) }
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone, V, C: Comparator<V>> VacantEntry<'a, K, V>
+    for VacEntry<'a, K, V, C>
+{
+    type Occupied = OccupEntry<'a, K, V, C>;
+
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(self, value: V) -> &'a mut V {
+        PriorityMut::into_mut(self.insert(value))
+    }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
+}
+
+impl<'a, K, V, C, Q: ?Sized + ToOwned<Owned = K>> VacEntryRef<'a, K, V, C, Q> {
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    pub fn insert(self, value: V) -> PriorityMut<'a, K, V, C>
+    where
+        K: Eq + Hash + Clone,
+        C: Comparator<V>,
+    {
+        let key: K = self.entry.into_key();
+        let (index, changed_indices) = self.heap.heap.insert((key.clone(), value));
+        let map = unsafe { &mut *self.map };
+        map.insert(key, index);
+        reindex(map, changed_indices);
+        unsafe { PriorityMut::new(self.map, self.heap, index) }
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V, C>
+    where
+        K: Eq + Hash + Clone,
+        C: Comparator<V>,
+    {
+        let key: K = self.entry.into_key();
+        let (index, changed_indices) = self.heap.heap.insert((key.clone(), value));
+        let map = unsafe { &mut *self.map };
+        map.insert(key.clone(), index);
+        reindex(map, changed_indices);
+        unsafe { Entry::new(self.map, self.heap, Occupied::new(key)) }
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone, V, C: Comparator<V>, Q: ?Sized + ToOwned<Owned = K>>
+    VacantEntry<'a, K, V> for VacEntryRef<'a, K, V, C, Q>
+{
+    type Occupied = OccupEntry<'a, K, V, C>;
+
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(self, value: V) -> &'a mut V {
+        PriorityMut::into_mut(self.insert(value))
+    }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
+}
+
+impl<'a, K: Borrow<Q>, V, C, T: Borrow<Q>, Q: ?Sized> Entry<'a, K, V, C, Occupied<T, Q>> {
+    pub fn key(&self) -> &K
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+    {
+        &self.heap.heap[self.index()].0
+    }
+
+    fn index(&self) -> Index
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+    {
+        let map = unsafe { &*self.map };
+        map[self.entry.key.borrow()]
+    }
+
+    fn settle(&mut self) -> Index
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+        C: Comparator<V>,
+    {
+        let map = unsafe { &mut *self.map };
+        self.heap.settle(map);
+        map[self.entry.key.borrow()]
+    }
+
+    pub fn insert(&mut self, value: V) -> V
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+        C: Comparator<V>,
+    {
+        let index = self.settle();
+        let map = unsafe { &mut *self.map };
+        let result = mem::replace(&mut self.heap.heap[index].1, value);
+        reindex(map, self.heap.heap.update(index));
+        result
+    }
+
+    pub fn remove(self) -> V
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+        C: Comparator<V>,
+    {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(mut self) -> (K, V)
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+        C: Comparator<V>,
+    {
+        let index = self.settle();
+        let map = unsafe { &mut *self.map };
+        map.remove(self.entry.key.borrow());
+        let ((key, result), changed_indices) = self.heap.heap.remove(index);
+        reindex(map, changed_indices);
+        (key, result)
+    }
+
+    pub fn get(&self) -> &V
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+    {
+        &self.heap.heap[self.index()].1
+    }
+
+    pub fn get_mut(&mut self) -> PriorityMut<'_, K, V, C>
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+        C: Comparator<V>,
+    {
+        let index = self.settle();
+        unsafe { PriorityMut::new(self.map, self.heap, index) }
+    }
+
+    pub fn into_mut(mut self) -> PriorityMut<'a, K, V, C>
+    where
+        K: Eq + Hash,
+        Q: Eq + Hash,
+        C: Comparator<V>,
+    {
+        let index = self.settle();
+        unsafe { PriorityMut::new(self.map, self.heap, index) }
+    }
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<V>, T, Q> OccupiedEntry<'a, K, V>
+    for Entry<'a, K, V, C, Occupied<T, Q>>
+where
+    K: Borrow<Q>,
+    T: Borrow<Q>,
+    Q: ?Sized + Eq + Hash,
+{
+    fn key(&self) -> &K {
+        self.key()
+    }
+
+    fn insert(&mut self, value: V) -> V {
+        self.insert(value)
+    }
+
+    fn remove(self) -> V {
+        self.remove()
+    }
+
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
+    fn get(&self) -> &V {
+        self.get()
+    }
+
+    fn get_mut(&mut self) -> &mut V {
+        PriorityMut::into_mut(self.get_mut())
+    }
+
+    fn into_mut(self) -> &'a mut V {
+        PriorityMut::into_mut(self.into_mut())
+    }
+}
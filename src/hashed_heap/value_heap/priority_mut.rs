@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+use super::{reindex, Pending, PriorityHeap};
+use crate::hashed_heap::comparator::Comparator;
+use crate::hashed_heap::indexed_heap::Index;
+
+pub struct PriorityMut<'a, K: Eq + Hash, V, C: Comparator<V>> {
+    map: *mut HashMap<K, Index>,
+    heap: &'a mut PriorityHeap<K, V, C>,
+    index: Index,
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<V>> PriorityMut<'a, K, V, C> {
+    pub(super) unsafe fn new(
+        map: *mut HashMap<K, Index>,
+        heap: &'a mut PriorityHeap<K, V, C>,
+        index: Index,
+    ) -> Self {
+        Self { map, heap, index }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.heap.heap[self.index].0
+    }
+
+    pub fn into_mut(this: Self) -> &'a mut V {
+        let this = ManuallyDrop::new(this);
+        let heap = unsafe { ptr::read(&this.heap) };
+        heap.pending = Pending::One(this.index);
+        &mut heap.heap[this.index].1
+    }
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<V>> Deref for PriorityMut<'a, K, V, C> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.heap.heap[self.index].1
+    }
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<V>> DerefMut for PriorityMut<'a, K, V, C> {
+    fn deref_mut(&mut self) -> &mut V {
+        &mut self.heap.heap[self.index].1
+    }
+}
+
+impl<'a, K: Eq + Hash, V, C: Comparator<V>> Drop for PriorityMut<'a, K, V, C> {
+    fn drop(&mut self) {
+        let map = unsafe { &mut *self.map };
+        reindex(map, self.heap.heap.update(self.index));
+    }
+}
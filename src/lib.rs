@@ -1,19 +1,29 @@
 use clear::Clear;
 use drain::Drain;
 
-pub use self::hashed_heap::{HashedMaxHeap, HashedMinHeap};
+pub use self::hashed_heap::IndexedPriorityQueue;
+pub use self::hashed_heap::{
+    HashedMaxHeap, HashedMinHeap, HashedMinMaxHeap, HashedRadixHeap, HashedValueMaxHeap,
+    HashedValueMinHeap,
+};
 pub use self::rollover_map::RolloverMap;
+pub use self::rollover_set::RolloverSet;
 
 mod btree_map_impl;
+mod btree_set_impl;
 mod hash_map_impl;
+mod hash_set_impl;
 
 pub mod clear;
 pub mod drain;
+pub mod entry_ref;
 pub mod hashed_heap;
 pub mod rollover_map;
+pub mod rollover_set;
 
-pub trait GenericMap:
-    Default + Extend<(Self::K, Self::V)> + IntoIterator<Item = (Self::K, Self::V)>
+pub trait GenericMap: Extend<(Self::K, Self::V)>
+    + IntoIterator<Item = (Self::K, Self::V)>
+    + Lookup<<Self as GenericMap>::K, Key = <Self as GenericMap>::K, Value = <Self as GenericMap>::V>
 {
     type K;
     type V;
@@ -30,7 +40,7 @@ pub trait GenericMap:
     type DrainIter<'a>: Iterator<Item = (Self::K, Self::V)>
     where
         Self: 'a;
-    type VacEntry<'a>: VacantEntry<'a, Self::K, Self::V>
+    type VacEntry<'a>: VacantEntry<'a, Self::K, Self::V, Occupied = Self::OccupEntry<'a>>
     where
         Self: 'a;
     type OccupEntry<'a>: OccupiedEntry<'a, Self::K, Self::V>
@@ -39,32 +49,69 @@ pub trait GenericMap:
 
     fn len(&self) -> usize;
     fn is_empty(&self) -> bool;
-    fn contains_key(&self, key: &Self::K) -> bool;
-    fn get(&self, key: &Self::K) -> Option<&Self::V>;
-    fn get_mut(&mut self, key: &Self::K) -> Option<&mut Self::V>;
     fn insert(&mut self, key: Self::K, value: Self::V) -> Option<Self::V>;
-    fn remove(&mut self, key: &Self::K) -> Option<Self::V>;
     fn drain(&mut self) -> Self::DrainIter<'_>;
     fn entry(&mut self, key: Self::K) -> Entry<Self::VacEntry<'_>, Self::OccupEntry<'_>>;
     fn iter(&self) -> Self::Iter<'_>;
     fn iter_mut(&mut self) -> Self::IterMut<'_>;
-    fn remove_clearable(&mut self, key: &Self::K) -> bool
+}
+
+pub trait Lookup<Q: ?Sized> {
+    type Key;
+    type Value;
+
+    fn contains_key(&self, key: &Q) -> bool;
+    fn get(&self, key: &Q) -> Option<&Self::Value>;
+    fn get_key_value(&self, key: &Q) -> Option<(&Self::Key, &Self::Value)>;
+    fn get_mut(&mut self, key: &Q) -> Option<&mut Self::Value>;
+    fn remove(&mut self, key: &Q) -> Option<Self::Value>;
+    fn remove_entry(&mut self, key: &Q) -> Option<(Self::Key, Self::Value)>;
+    fn remove_clearable(&mut self, key: &Q) -> bool
     where
-        Self::V: Clear,
+        Self::Value: Clear,
     {
         self.remove(key).is_some()
     }
     fn drain_or_remove(
         &mut self,
-        key: &Self::K,
-    ) -> Option<DrainOrRemove<<Self::V as Drain>::Output<'_>, Self::V>>
+        key: &Q,
+    ) -> Option<DrainOrRemove<<Self::Value as Drain>::Output<'_>, Self::Value>>
     where
-        Self::V: Drain,
+        Self::Value: Drain,
     {
         self.remove(key).map(DrainOrRemove::Removed)
     }
 }
 
+pub trait GenericSet:
+    Extend<Self::T>
+    + IntoIterator<Item = Self::T>
+    + SetLookup<<Self as GenericSet>::T, Value = <Self as GenericSet>::T>
+{
+    type T;
+    type Iter<'a>: Iterator<Item = &'a Self::T>
+    where
+        Self::T: 'a,
+        Self: 'a;
+    type DrainIter<'a>: Iterator<Item = Self::T>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool;
+    fn insert(&mut self, value: Self::T) -> bool;
+    fn drain(&mut self) -> Self::DrainIter<'_>;
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+pub trait SetLookup<Q: ?Sized> {
+    type Value;
+
+    fn contains(&self, value: &Q) -> bool;
+    fn remove(&mut self, value: &Q) -> bool;
+    fn take(&mut self, value: &Q) -> Option<Self::Value>;
+}
+
 pub enum DrainOrRemove<Drained, Removed> {
     Drained(Drained),
     Removed(Removed),
@@ -75,15 +122,92 @@ pub enum Entry<V, O> {
     Occupied(O),
 }
 
+impl<E, O> Entry<E, O> {
+    pub fn key<'a, K, V>(&self) -> &K
+    where
+        E: VacantEntry<'a, K, V>,
+        O: OccupiedEntry<'a, K, V>,
+    {
+        match self {
+            Entry::Vacant(entry) => entry.key(),
+            Entry::Occupied(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert<'a, K, V>(self, default: V) -> &'a mut V
+    where
+        E: VacantEntry<'a, K, V>,
+        O: OccupiedEntry<'a, K, V>,
+    {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with<'a, K, V, F: FnOnce() -> V>(self, default: F) -> &'a mut V
+    where
+        E: VacantEntry<'a, K, V>,
+        O: OccupiedEntry<'a, K, V>,
+    {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with_key<'a, K, V, F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V
+    where
+        E: VacantEntry<'a, K, V>,
+        O: OccupiedEntry<'a, K, V>,
+    {
+        match self {
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_default<'a, K, V: Default>(self) -> &'a mut V
+    where
+        E: VacantEntry<'a, K, V>,
+        O: OccupiedEntry<'a, K, V>,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<'a, K, V, F: FnOnce(&mut V)>(mut self, f: F) -> Self
+    where
+        O: OccupiedEntry<'a, K, V>,
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
 pub trait VacantEntry<'a, K, V> {
+    type Occupied: OccupiedEntry<'a, K, V>;
+
     fn key(&self) -> &K;
     fn insert(self, value: V) -> &'a mut V;
+    fn insert_entry(self, value: V) -> Self::Occupied;
 }
 
 pub trait OccupiedEntry<'a, K, V> {
     fn key(&self) -> &K;
     fn insert(&mut self, value: V) -> V;
     fn remove(self) -> V;
+    fn remove_entry(self) -> (K, V);
+    fn replace_entry(mut self, key: K, value: V) -> (K, V)
+    where
+        Self: Sized,
+    {
+        (key, self.insert(value))
+    }
     fn get(&self) -> &V;
     fn get_mut(&mut self) -> &mut V;
     fn into_mut(self) -> &'a mut V;
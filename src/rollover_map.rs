@@ -1,58 +1,115 @@
-use std::{array, collections::HashMap, iter, mem, slice};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::{iter, mem, slice};
 
 use arrayvec::ArrayVec;
 
+use crate::entry_ref::{EntryRef, KeySource, PendingKey};
+use crate::hashed_heap::backend::{Binary, DoubleEndedBackend, HeapBackend, MinMax};
 use crate::hashed_heap::comparator::{self, Comparator};
-use crate::{clear::Clear, drain::Drain, DrainOrRemove, Entry, GenericMap};
+use crate::hashed_heap::{HashedHeap, StableHashedHeap};
+use crate::{
+    clear::Clear, drain::Drain, DrainOrRemove, Entry, GenericMap, Lookup, OccupiedEntry,
+    VacantEntry,
+};
 
-use self::take_iter::TakeIter;
+use self::lookup::{Fingerprinted, Linear, Ordered, Ranked, Sorted, StackFind, StackLookup};
+use self::recycle::{NoRecycle, Recycle};
 
 pub use self::occup_entry::OccupEntry;
+pub use self::sorted::{DrainSorted, SortedIter};
+pub use self::spill_policy::{FixedSpill, SpillControl, SpillPolicy, TunableSpill};
+pub use self::stats::{Observed, SpillEvent, SpillHook, SpillStats};
 pub use self::vac_entry::VacEntry;
 
+pub mod lookup;
+pub mod recycle;
+
 mod occup_entry;
-mod take_iter;
+mod sorted;
+mod spill_policy;
+mod stats;
 mod vac_entry;
 
 #[derive(Debug)]
-pub struct RolloverMap<K, V, const N: usize = 1, M = HashMap<K, V>> {
+pub struct RolloverMap<
+    K,
+    V,
+    const N: usize = 1,
+    M = HashMap<K, V>,
+    L = Linear,
+    R = NoRecycle,
+    P = FixedSpill,
+> {
     stack_keys: ArrayVec<K, N>,
-    stack_values: [V; N],
+    stack_values: ArrayVec<V, N>,
+    stack_lookup: L,
     heap: M,
+    recycled: R,
+    control: P,
 }
 
-pub type RolloverHashedOptHeap<K, V, O, const N: usize = 1> =
-    RolloverMap<K, V, N, crate::hashed_heap::HashedHeap<K, V, O>>;
-pub type RolloverHashedMaxHeap<K, V, const N: usize = 1> =
-    RolloverHashedOptHeap<K, V, comparator::Max<K>, N>;
-pub type RolloverHashedMinHeap<K, V, const N: usize = 1> =
-    RolloverHashedOptHeap<K, V, comparator::Min<K>, N>;
+pub type FingerprintedRolloverMap<K, V, const N: usize = 1, M = HashMap<K, V>> =
+    RolloverMap<K, V, N, M, Fingerprinted<N>>;
 
-impl<K, V, const N: usize, M: Default> Default for RolloverMap<K, V, N, M>
-where
-    [V; N]: Default,
+pub type SortedRolloverMap<K, V, const N: usize = 1> = RolloverMap<K, V, N, BTreeMap<K, V>, Sorted>;
+
+pub type RecyclingRolloverMap<K, V, const N: usize = 1, M = HashMap<K, V>> =
+    RolloverMap<K, V, N, M, Linear, ArrayVec<V, N>>;
+
+pub type ObservedRolloverMap<K, V, const N: usize = 1, M = HashMap<K, V>, P = FixedSpill> =
+    RolloverMap<K, V, N, M, Linear, NoRecycle, Observed<P>>;
+
+pub type RolloverHashedOptHeap<
+    K,
+    V,
+    O,
+    const N: usize = 1,
+    B = Binary,
+    S = RandomState,
+    P = FixedSpill,
+> = RolloverMap<K, V, N, HashedHeap<K, V, O, B, S>, Ranked, NoRecycle, P>;
+pub type RolloverHashedMaxHeap<K, V, const N: usize = 1, S = RandomState, P = FixedSpill> =
+    RolloverHashedOptHeap<K, V, comparator::Max<K>, N, Binary, S, P>;
+pub type RolloverHashedMinHeap<K, V, const N: usize = 1, S = RandomState, P = FixedSpill> =
+    RolloverHashedOptHeap<K, V, comparator::Min<K>, N, Binary, S, P>;
+pub type RolloverHashedMinMaxHeap<K, V, const N: usize = 1, S = RandomState, P = FixedSpill> =
+    RolloverHashedOptHeap<K, V, comparator::Min<K>, N, MinMax, S, P>;
+pub type RolloverStableHashedHeap<K, V, O, const N: usize = 1, B = Binary, P = FixedSpill> =
+    RolloverMap<K, V, N, StableHashedHeap<K, V, O, B>, Ordered, NoRecycle, P>;
+
+impl<K, V, const N: usize, M: Default, L: Default, R: Default, P: SpillControl> Default
+    for RolloverMap<K, V, N, M, L, R, P>
 {
     fn default() -> Self {
         Self {
             stack_keys: ArrayVec::new(),
-            stack_values: Default::default(),
+            stack_values: ArrayVec::new(),
+            stack_lookup: L::default(),
             heap: Default::default(),
+            recycled: R::default(),
+            control: P::fixed(N),
         }
     }
 }
 
-impl<K, V: Clear, const N: usize, M: Clear> Clear for RolloverMap<K, V, N, M> {
+impl<K, V: Clear, const N: usize, M: Clear, L: Clear, R, P> Clear
+    for RolloverMap<K, V, N, M, L, R, P>
+{
     fn clear(&mut self) {
-        let nkeys = self.stack_keys.len();
-        self.stack_keys.clear();
-        for v in self.stack_values[..nkeys].iter_mut() {
-            v.clear();
+        for value in &mut self.stack_values[..self.stack_keys.len()] {
+            value.clear();
         }
+        self.stack_keys.clear();
+        self.stack_lookup.clear();
         self.heap.clear();
     }
 }
 
-impl<K, V: Default, const N: usize, M: GenericMap<K = K, V = V>> Drain for RolloverMap<K, V, N, M> {
+impl<K, V, const N: usize, M: GenericMap<K = K, V = V>, L: StackLookup<K, M>, R, P> Drain
+    for RolloverMap<K, V, N, M, L, R, P>
+{
     type Output<'a> = DrainIter<'a, K, V, N, M::DrainIter<'a>>
     where
         Self: 'a;
@@ -62,18 +119,18 @@ impl<K, V: Default, const N: usize, M: GenericMap<K = K, V = V>> Drain for Rollo
     }
 }
 
-impl<K, V, const N: usize, M: IntoIterator<Item = (K, V)>> IntoIterator
-    for RolloverMap<K, V, N, M>
+impl<K, V, const N: usize, M: IntoIterator<Item = (K, V)>, L, R, P> IntoIterator
+    for RolloverMap<K, V, N, M, L, R, P>
 {
     type Item = (K, V);
     type IntoIter =
-        iter::Chain<iter::Zip<arrayvec::IntoIter<K, N>, array::IntoIter<V, N>>, M::IntoIter>;
+        iter::Chain<iter::Zip<arrayvec::IntoIter<K, N>, arrayvec::IntoIter<V, N>>, M::IntoIter>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.stack_keys
             .into_iter()
-            .zip(self.stack_values.into_iter())
-            .chain(self.heap.into_iter())
+            .zip(self.stack_values)
+            .chain(self.heap)
     }
 }
 
@@ -81,12 +138,10 @@ pub type Iter<'a, K, V, I> = iter::Chain<iter::Zip<slice::Iter<'a, K>, slice::It
 pub type IterMut<'a, K, V, I> =
     iter::Chain<iter::Zip<slice::Iter<'a, K>, slice::IterMut<'a, V>>, I>;
 pub type DrainIter<'a, K, V, const N: usize, I> =
-    iter::Chain<iter::Zip<arrayvec::Drain<'a, K, N>, TakeIter<'a, V>>, I>;
+    iter::Chain<iter::Zip<arrayvec::Drain<'a, K, N>, arrayvec::Drain<'a, V, N>>, I>;
 
-impl<'a, K: Eq, V: Default, const N: usize, M: GenericMap<K = K, V = V>> IntoIterator
-    for &'a RolloverMap<K, V, N, M>
-where
-    [V; N]: Default,
+impl<'a, K: Eq, V, const N: usize, M: GenericMap<K = K, V = V>, L, R, P> IntoIterator
+    for &'a RolloverMap<K, V, N, M, L, R, P>
 {
     type Item = (&'a K, &'a V);
     type IntoIter = Iter<'a, K, V, M::Iter<'a>>;
@@ -96,10 +151,8 @@ where
     }
 }
 
-impl<'a, K: Eq, V: Default, const N: usize, M: GenericMap<K = K, V = V>> IntoIterator
-    for &'a mut RolloverMap<K, V, N, M>
-where
-    [V; N]: Default,
+impl<'a, K: Eq, V, const N: usize, M: GenericMap<K = K, V = V>, L, R, P> IntoIterator
+    for &'a mut RolloverMap<K, V, N, M, L, R, P>
 {
     type Item = (&'a K, &'a mut V);
     type IntoIter = IterMut<'a, K, V, M::IterMut<'a>>;
@@ -109,39 +162,172 @@ where
     }
 }
 
-impl<K: Eq, V: Default, const N: usize, M: GenericMap<K = K, V = V>> Extend<(K, V)>
-    for RolloverMap<K, V, N, M>
+impl<K: Eq, V, const N: usize, M, L, R, P> Extend<(K, V)> for RolloverMap<K, V, N, M, L, R, P>
+where
+    M: GenericMap<K = K, V = V>,
+    L: StackLookup<K, M>,
+    P: SpillControl,
 {
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
         let mut iter = iter.into_iter();
-        if self.heap.is_empty() {
-            for ((key, value), val) in
-                (&mut iter).zip(self.stack_values[self.stack_keys.len()..].iter_mut())
-            {
-                self.stack_keys.push(key);
-                *val = value;
-            }
+        while self.heap.is_empty() {
+            let Some((key, value)) = iter.next() else {
+                return;
+            };
+            self.insert(key, value);
         }
         self.heap.extend(iter);
-        if !self.heap.is_empty() {
-            self.heap.extend(
-                self.stack_keys
-                    .drain(..)
-                    .zip(self.stack_values.iter_mut().map(mem::take)),
-            );
+        self.control.grew(self.heap.len());
+    }
+}
+
+fn spill<K, V, const N: usize, M: Extend<(K, V)>, L: StackLookup<K, M>, P: SpillControl>(
+    stack_keys: &mut ArrayVec<K, N>,
+    stack_values: &mut ArrayVec<V, N>,
+    stack_lookup: &mut L,
+    control: &mut P,
+    heap: &mut M,
+) {
+    let len = stack_keys.len();
+    control.spilled(len);
+    stack_lookup.clear();
+    heap.extend(stack_keys.drain(..).zip(stack_values.drain(..len)));
+}
+
+fn unspill<K, V, const N: usize, M, L: StackLookup<K, M>, P: SpillControl>(
+    heap_len: usize,
+    heap: &mut M,
+    drain: impl FnOnce(&mut M) -> ArrayVec<(K, V), N>,
+    stack_keys: &mut ArrayVec<K, N>,
+    stack_values: &mut ArrayVec<V, N>,
+    stack_lookup: &mut L,
+    control: &mut P,
+) {
+    if control.policy(N).should_unspill(heap_len) {
+        for (k, v) in drain(heap) {
+            push_stack(stack_keys, stack_values, stack_lookup, heap, k, v);
         }
+        control.collapsed(stack_keys.len());
+    }
+}
+
+fn push_stack<K, V, const N: usize, M, L: StackLookup<K, M>>(
+    stack_keys: &mut ArrayVec<K, N>,
+    stack_values: &mut ArrayVec<V, N>,
+    stack_lookup: &mut L,
+    heap: &M,
+    key: K,
+    value: V,
+) -> usize {
+    let index = stack_lookup.insert(stack_keys, &key, heap);
+    let live = stack_keys.len();
+    if live < stack_values.len() {
+        stack_values[live] = value;
+    } else {
+        stack_values.push(value);
+    }
+    stack_values[index..=live].rotate_right(1);
+    stack_keys.insert(index, key);
+    index
+}
+
+fn take_stack<K, V, const N: usize, M, L: StackLookup<K, M>>(
+    stack_keys: &mut ArrayVec<K, N>,
+    stack_values: &mut ArrayVec<V, N>,
+    stack_lookup: &mut L,
+    heap: &M,
+    index: usize,
+) -> (K, V) {
+    let entry = if L::ORDERED {
+        (stack_keys.remove(index), stack_values.remove(index))
+    } else {
+        let last = stack_keys.len() - 1;
+        stack_values.swap(index, last);
+        (stack_keys.swap_remove(index), stack_values.remove(last))
+    };
+    stack_lookup.remove(stack_keys, index, heap);
+    entry
+}
+
+fn retire_stack<'a, K, V, const N: usize, M, L: StackLookup<K, M>>(
+    stack_keys: &mut ArrayVec<K, N>,
+    stack_values: &'a mut ArrayVec<V, N>,
+    stack_lookup: &mut L,
+    heap: &M,
+    index: usize,
+) -> &'a mut V {
+    let last = stack_keys.len() - 1;
+    if L::ORDERED {
+        stack_keys.remove(index);
+        stack_values[index..=last].rotate_left(1);
+    } else {
+        stack_keys.swap_remove(index);
+        stack_values.swap(index, last);
     }
+    stack_lookup.remove(stack_keys, index, heap);
+    &mut stack_values[last]
 }
 
-impl<K, V, const N: usize, M> RolloverMap<K, V, N, M> {
+impl<K, V, const N: usize, M, L, R, P> RolloverMap<K, V, N, M, L, R, P> {
     pub fn new() -> Self
     where
         M: Default,
-        [V; N]: Default,
+        L: Default,
+        R: Default,
+        P: SpillControl,
     {
         Self::default()
     }
 
+    pub fn with_policy(policy: SpillPolicy) -> Self
+    where
+        M: Default,
+        L: Default,
+        R: Default,
+        P: TunableSpill,
+    {
+        let mut map = Self::default();
+        map.control.set_policy(Self::checked(policy));
+        map
+    }
+
+    pub fn policy(&self) -> SpillPolicy
+    where
+        P: SpillControl,
+    {
+        self.control.policy(N)
+    }
+
+    pub fn set_policy(&mut self, policy: SpillPolicy)
+    where
+        L: StackLookup<K, M>,
+        M: GenericMap<K = K, V = V>,
+        P: TunableSpill,
+    {
+        self.control.set_policy(Self::checked(policy));
+        if self.heap.is_empty() {
+            if !policy.fits_stack(self.stack_keys.len()) {
+                spill(
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &mut self.control,
+                    &mut self.heap,
+                );
+            }
+        } else {
+            unspill(
+                self.heap.len(),
+                &mut self.heap,
+                |heap| heap.drain().collect(),
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.control,
+            );
+        }
+    }
+
     pub fn len(&self) -> usize
     where
         M: GenericMap,
@@ -149,6 +335,35 @@ impl<K, V, const N: usize, M> RolloverMap<K, V, N, M> {
         self.stack_keys.len() + self.heap.len()
     }
 
+    pub fn is_spilled(&self) -> bool
+    where
+        M: GenericMap,
+    {
+        !self.heap.is_empty()
+    }
+
+    pub fn inline_len(&self) -> usize {
+        self.stack_keys.len()
+    }
+
+    pub fn backing_map(&self) -> &M {
+        &self.heap
+    }
+
+    pub fn recycled_len(&self) -> usize
+    where
+        R: Recycle<V>,
+    {
+        self.recycled.len()
+    }
+
+    pub fn clear_recycled(&mut self)
+    where
+        R: Recycle<V>,
+    {
+        self.recycled.clear();
+    }
+
     pub fn is_empty(&self) -> bool
     where
         M: GenericMap,
@@ -156,145 +371,193 @@ impl<K, V, const N: usize, M> RolloverMap<K, V, N, M> {
         self.stack_keys.is_empty() && self.heap.is_empty()
     }
 
-    pub fn contains_key(&self, key: &K) -> bool
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
     where
-        K: PartialEq,
-        M: GenericMap<K = K>,
+        L: StackFind<K, Q>,
+        M: Lookup<Q>,
     {
-        self.stack_keys.contains(key) || self.heap.contains_key(key)
+        self.stack_lookup.find(&self.stack_keys, key).is_some() || self.heap.contains_key(key)
     }
 
-    pub fn get(&self, key: &K) -> Option<&V>
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
     where
-        K: PartialEq,
-        M: GenericMap<K = K, V = V>,
+        L: StackFind<K, Q>,
+        M: Lookup<Q, Value = V>,
     {
-        for (k, v) in self.stack_keys.iter().zip(self.stack_values.iter()) {
-            if k == key {
-                return Some(v);
-            }
+        match self.stack_lookup.find(&self.stack_keys, key) {
+            Some(i) => Some(&self.stack_values[i]),
+            None => self.heap.get(key),
         }
-        self.heap.get(key)
     }
 
-    pub fn get_mut(&mut self, key: &K) -> Option<&mut V>
+    pub fn get_key_value<Q: ?Sized>(&self, key: &Q) -> Option<(&K, &V)>
     where
-        K: PartialEq,
-        M: GenericMap<K = K, V = V>,
+        L: StackFind<K, Q>,
+        M: Lookup<Q, Key = K, Value = V>,
     {
-        for (k, v) in self.stack_keys.iter().zip(self.stack_values.iter_mut()) {
-            if k == key {
-                return Some(v);
-            }
+        match self.stack_lookup.find(&self.stack_keys, key) {
+            Some(i) => Some((&self.stack_keys[i], &self.stack_values[i])),
+            None => self.heap.get_key_value(key),
+        }
+    }
+
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        L: StackFind<K, Q>,
+        M: Lookup<Q, Value = V>,
+    {
+        match self.stack_lookup.find(&self.stack_keys, key) {
+            Some(i) => Some(&mut self.stack_values[i]),
+            None => self.heap.get_mut(key),
         }
-        self.heap.get_mut(key)
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V>
     where
-        K: PartialEq,
-        V: Default,
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
     {
-        for (k, v) in self.stack_keys.iter_mut().zip(self.stack_values.iter_mut()) {
-            if k == &key {
-                return Some(mem::replace(v, value));
-            }
+        if let Some(i) = self.stack_lookup.find(&self.stack_keys, &key) {
+            return Some(mem::replace(&mut self.stack_values[i], value));
         }
         if self.heap.is_empty() {
-            if self.stack_keys.len() < N {
-                self.stack_keys.push(key);
-                self.stack_values[self.stack_keys.len() - 1] = value;
+            if self.control.policy(N).fits_stack(self.stack_keys.len() + 1) {
+                push_stack(
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &self.heap,
+                    key,
+                    value,
+                );
+                self.control.grew(self.stack_keys.len());
                 return None;
             }
-            for (k, v) in self
-                .stack_keys
-                .drain(..)
-                .zip(self.stack_values.iter_mut().map(mem::take))
-            {
-                self.heap.insert(k, v);
-            }
+            spill(
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.control,
+                &mut self.heap,
+            );
         }
-        self.heap.insert(key, value)
+        let result = self.heap.insert(key, value);
+        if result.is_none() {
+            self.control.grew(self.heap.len());
+        }
+        result
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V>
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
     where
-        K: PartialEq,
-        V: Default,
-        M: GenericMap<K = K, V = V>,
+        L: StackLookup<K, M> + StackFind<K, Q>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V> + Lookup<Q, Key = K, Value = V>,
     {
-        for (i, (k, v)) in self
-            .stack_keys
-            .iter()
-            .zip(self.stack_values.iter_mut())
-            .enumerate()
-        {
-            if k == key {
-                self.stack_keys.remove(i);
-                let result = mem::take(v);
-                self.stack_values[i..].rotate_left(1);
-                return Some(result);
-            }
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        L: StackLookup<K, M> + StackFind<K, Q>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V> + Lookup<Q, Key = K, Value = V>,
+    {
+        if let Some(i) = self.stack_lookup.find(&self.stack_keys, key) {
+            return Some(self.remove_stack_entry(i));
         }
-        let result = self.heap.remove(key);
-        if self.heap.len() == N {
-            for ((k, v), val) in self.heap.drain().zip(self.stack_values.iter_mut()) {
-                self.stack_keys.push(k);
-                *val = v;
-            }
+        let result = self.heap.remove_entry(key);
+        if result.is_some() {
+            unspill(
+                self.heap.len(),
+                &mut self.heap,
+                |heap| heap.drain().collect(),
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.control,
+            );
         }
         result
     }
 
     pub fn drain(&mut self) -> DrainIter<'_, K, V, N, M::DrainIter<'_>>
     where
+        L: StackLookup<K, M>,
         M: GenericMap<K = K, V = V>,
-        V: Default,
     {
-        let nelems = self.stack_keys.len();
+        let len = self.stack_keys.len();
+        self.stack_lookup.clear();
         self.stack_keys
             .drain(..)
-            .zip(TakeIter::new(&mut self.stack_values[..nelems]))
+            .zip(self.stack_values.drain(..len))
             .chain(self.heap.drain())
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn entry(
         &mut self,
         key: K,
     ) -> Entry<
-        VacEntry<'_, K, V, N, M, M::VacEntry<'_>>,
-        OccupEntry<'_, K, V, N, M, M::OccupEntry<'_>>,
+        VacEntry<'_, K, V, N, M, M::VacEntry<'_>, L, R, P>,
+        OccupEntry<'_, K, V, N, M, M::OccupEntry<'_>, L, R, P>,
     >
     where
-        K: PartialEq,
+        L: StackLookup<K, M>,
         M: GenericMap<K = K>,
     {
-        for (i, (k, v)) in self
-            .stack_keys
-            .iter()
-            .zip(self.stack_values.iter_mut())
-            .enumerate()
-        {
-            if k == &key {
-                return Entry::Occupied(unsafe {
-                    OccupEntry::stack(i, k, v, &mut self.stack_keys, &mut self.stack_values)
-                });
-            }
+        if let Some(i) = self.stack_lookup.find(&self.stack_keys, &key) {
+            let k = &self.stack_keys[i] as *const K;
+            let v = &mut self.stack_values[i] as *mut V;
+            return Entry::Occupied(unsafe {
+                OccupEntry::stack(
+                    i,
+                    k,
+                    v,
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &self.heap,
+                )
+            });
         }
         if self.heap.is_empty() {
             return Entry::Vacant(VacEntry::stack(
                 key,
                 &mut self.stack_keys,
                 &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.recycled,
+                &mut self.control,
                 &mut self.heap,
             ));
         }
         let heap_ptr = &mut self.heap as *mut M;
+        let len = self.heap.len();
         match self.heap.entry(key) {
-            Entry::Vacant(v) => Entry::Vacant(VacEntry::heap(v)),
+            Entry::Vacant(v) => Entry::Vacant(unsafe {
+                VacEntry::heap(
+                    heap_ptr,
+                    v,
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &mut self.recycled,
+                    &mut self.control,
+                    len + 1,
+                )
+            }),
             Entry::Occupied(o) => Entry::Occupied(unsafe {
-                OccupEntry::heap(heap_ptr, o, &mut self.stack_keys, &mut self.stack_values)
+                OccupEntry::heap(
+                    heap_ptr,
+                    o,
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &mut self.recycled,
+                    &mut self.control,
+                )
             }),
         }
     }
@@ -319,64 +582,155 @@ impl<K, V, const N: usize, M> RolloverMap<K, V, N, M> {
             .chain(self.heap.iter_mut())
     }
 
-    fn remove_clearable(&mut self, key: &K) -> bool
+    fn checked(policy: SpillPolicy) -> SpillPolicy {
+        assert!(
+            policy.max_stack_len() <= N,
+            "maximum stack length {} exceeds capacity {}",
+            policy.max_stack_len(),
+            N
+        );
+        policy
+    }
+
+    fn remove_stack_entry(&mut self, index: usize) -> (K, V)
+    where
+        L: StackLookup<K, M>,
+    {
+        take_stack(
+            &mut self.stack_keys,
+            &mut self.stack_values,
+            &mut self.stack_lookup,
+            &self.heap,
+            index,
+        )
+    }
+
+    fn retire_stack_entry(&mut self, index: usize) -> &mut V
+    where
+        L: StackLookup<K, M>,
+    {
+        retire_stack(
+            &mut self.stack_keys,
+            &mut self.stack_values,
+            &mut self.stack_lookup,
+            &self.heap,
+            index,
+        )
+    }
+
+    fn remove_clearable<Q: ?Sized>(&mut self, key: &Q) -> bool
     where
-        K: PartialEq,
         V: Clear,
-        M: GenericMap<K = K, V = V>,
+        R: Recycle<V>,
+        L: StackLookup<K, M> + StackFind<K, Q>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V> + Lookup<Q, Value = V>,
     {
-        for (i, (k, v)) in self
-            .stack_keys
-            .iter()
-            .zip(self.stack_values.iter_mut())
-            .enumerate()
-        {
-            if k == key {
-                self.stack_keys.remove(i);
-                v.clear();
-                self.stack_values[i..].rotate_left(1);
-                return true;
-            }
+        if let Some(i) = self.stack_lookup.find(&self.stack_keys, key) {
+            self.retire_stack_entry(i).clear();
+            return true;
         }
-        let result = self.heap.remove(key).is_some();
-        if self.heap.len() == N {
-            for ((k, v), val) in self.heap.drain().zip(self.stack_values.iter_mut()) {
-                self.stack_keys.push(k);
-                *val = v;
-            }
+        let Some(value) = self.heap.remove(key) else {
+            return false;
+        };
+        if let Ok(value) = self.recycled.keep(value) {
+            value.clear();
         }
-        result
+        unspill(
+            self.heap.len(),
+            &mut self.heap,
+            |heap| heap.drain().collect(),
+            &mut self.stack_keys,
+            &mut self.stack_values,
+            &mut self.stack_lookup,
+            &mut self.control,
+        );
+        true
     }
 
-    pub fn drain_or_remove(&mut self, key: &K) -> Option<DrainOrRemove<V::Output<'_>, V>>
+    pub fn drain_or_remove<Q: ?Sized>(&mut self, key: &Q) -> Option<DrainOrRemove<V::Output<'_>, V>>
     where
-        K: Eq,
         V: Drain,
-        M: GenericMap<K = K, V = V>,
+        L: StackLookup<K, M> + StackFind<K, Q>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V> + Lookup<Q, Value = V>,
     {
-        for (i, k) in self.stack_keys.iter().enumerate() {
-            if k == key {
-                self.stack_keys.remove(i);
-                self.stack_values[i..].rotate_left(1);
-                let result = self.stack_values[N - 1].drain();
-                return Some(DrainOrRemove::Drained(result));
-            }
+        if let Some(i) = self.stack_lookup.find(&self.stack_keys, key) {
+            return Some(DrainOrRemove::Drained(self.retire_stack_entry(i).drain()));
         }
         let result = self.heap.remove(key);
-        if self.heap.len() == N {
-            for ((k, v), val) in self.heap.drain().zip(self.stack_values.iter_mut()) {
-                self.stack_keys.push(k);
-                *val = v;
-            }
+        if result.is_some() {
+            unspill(
+                self.heap.len(),
+                &mut self.heap,
+                |heap| heap.drain().collect(),
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.control,
+            );
         }
         result.map(DrainOrRemove::Removed)
     }
 }
 
-impl<K: Eq, V: Default, const N: usize, M: GenericMap<K = K, V = V>> GenericMap
-    for RolloverMap<K, V, N, M>
+impl<K, V, const N: usize, M, L, R, P> RolloverMap<K, V, N, M, L, R, Observed<P>> {
+    pub fn stats(&self) -> Option<&SpillStats> {
+        self.control.stats()
+    }
+
+    pub fn enable_stats(&mut self)
+    where
+        M: GenericMap,
+    {
+        let len = self.len();
+        self.control.enable_stats(len);
+    }
+
+    pub fn disable_stats(&mut self) {
+        self.control.disable_stats();
+    }
+
+    pub fn set_spill_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(SpillEvent) + Send + 'static,
+    {
+        self.control.set_hook(Some(Box::new(hook)));
+    }
+
+    pub fn clear_spill_hook(&mut self) {
+        self.control.set_hook(None);
+    }
+}
+
+impl<'a, K, V, const N: usize, M, E, O, L, R, P, T>
+    Entry<VacEntry<'a, K, V, N, M, E, L, R, P, T>, OccupEntry<'a, K, V, N, M, O, L, R, P>>
 where
-    [V; N]: Default,
+    M: GenericMap<K = K, V = V>,
+    T: KeySource<K>,
+    E: VacantEntry<'a, K, V>,
+    O: OccupiedEntry<'a, K, V>,
+    L: StackLookup<K, M>,
+    P: SpillControl,
+    R: Recycle<V>,
+{
+    pub fn or_insert_cleared(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        match self {
+            Entry::Vacant(entry) => entry.insert_cleared(),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+}
+
+impl<K: Eq, V, const N: usize, M, L, R, P> GenericMap for RolloverMap<K, V, N, M, L, R, P>
+where
+    M: GenericMap<K = K, V = V>,
+    L: StackLookup<K, M>,
+    P: SpillControl,
+    R: Recycle<V>,
 {
     type K = K;
     type V = V;
@@ -392,11 +746,11 @@ where
     where
         Self: 'a;
 
-    type VacEntry<'a> = VacEntry<'a, K, V, N, M, M::VacEntry<'a>>
+    type VacEntry<'a> = VacEntry<'a, K, V, N, M, M::VacEntry<'a>, L, R, P>
     where
         Self: 'a;
 
-    type OccupEntry<'a> = OccupEntry<'a, K, V, N, M, M::OccupEntry<'a>>
+    type OccupEntry<'a> = OccupEntry<'a, K, V, N, M, M::OccupEntry<'a>, L, R, P>
     where
         Self: 'a;
 
@@ -408,26 +762,10 @@ where
         self.is_empty()
     }
 
-    fn contains_key(&self, key: &K) -> bool {
-        self.contains_key(key)
-    }
-
-    fn get(&self, key: &K) -> Option<&V> {
-        self.get(key)
-    }
-
-    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        self.get_mut(key)
-    }
-
     fn insert(&mut self, key: K, value: V) -> Option<V> {
         self.insert(key, value)
     }
 
-    fn remove(&mut self, key: &K) -> Option<V> {
-        self.remove(key)
-    }
-
     fn drain(&mut self) -> Self::DrainIter<'_> {
         self.drain()
     }
@@ -443,15 +781,50 @@ where
     fn iter_mut(&mut self) -> Self::IterMut<'_> {
         self.iter_mut()
     }
+}
+
+impl<K: Eq, Q: ?Sized, V, const N: usize, M, L, R, P> Lookup<Q> for RolloverMap<K, V, N, M, L, R, P>
+where
+    M: GenericMap<K = K, V = V> + Lookup<Q, Key = K, Value = V>,
+    L: StackLookup<K, M> + StackFind<K, Q>,
+    P: SpillControl,
+    R: Recycle<V>,
+{
+    type Key = K;
+    type Value = V;
 
-    fn remove_clearable(&mut self, key: &K) -> bool
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.get_key_value(key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        self.remove_entry(key)
+    }
+
+    fn remove_clearable(&mut self, key: &Q) -> bool
     where
         V: Clear,
     {
         self.remove_clearable(key)
     }
 
-    fn drain_or_remove(&mut self, key: &K) -> Option<DrainOrRemove<V::Output<'_>, V>>
+    fn drain_or_remove(&mut self, key: &Q) -> Option<DrainOrRemove<V::Output<'_>, V>>
     where
         V: Drain,
     {
@@ -459,25 +832,942 @@ where
     }
 }
 
-impl<K, V, C: Comparator<K>, const N: usize> RolloverHashedOptHeap<K, V, C, N> {
+impl<K: Eq, Q: ?Sized, V, const N: usize, M, L, R, P> EntryRef<Q>
+    for RolloverMap<K, V, N, M, L, R, P>
+where
+    Q: ToOwned<Owned = K>,
+    M: EntryRef<Q> + GenericMap<K = K, V = V>,
+    L: StackLookup<K, M> + StackFind<K, Q>,
+    P: SpillControl,
+    R: Recycle<V>,
+{
+    type VacEntryRef<'a> = VacEntry<'a, K, V, N, M, M::VacEntryRef<'a>, L, R, P, PendingKey<'a, K, Q>>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    type OccupEntryRef<'a> = OccupEntry<'a, K, V, N, M, M::OccupEntryRef<'a>, L, R, P>
+    where
+        Self: 'a,
+        Q: 'a;
+
+    fn entry_ref<'a>(
+        &'a mut self,
+        key: &'a Q,
+    ) -> Entry<Self::VacEntryRef<'a>, Self::OccupEntryRef<'a>> {
+        if let Some(i) = self.stack_lookup.find(&self.stack_keys, key) {
+            let k = &self.stack_keys[i] as *const K;
+            let v = &mut self.stack_values[i] as *mut V;
+            return Entry::Occupied(unsafe {
+                OccupEntry::stack(
+                    i,
+                    k,
+                    v,
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &self.heap,
+                )
+            });
+        }
+        if self.heap.is_empty() {
+            return Entry::Vacant(VacEntry::stack(
+                PendingKey::new(key),
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.recycled,
+                &mut self.control,
+                &mut self.heap,
+            ));
+        }
+        let heap_ptr = &mut self.heap as *mut M;
+        let len = self.heap.len();
+        match self.heap.entry_ref(key) {
+            Entry::Vacant(v) => Entry::Vacant(unsafe {
+                VacEntry::heap(
+                    heap_ptr,
+                    v,
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &mut self.recycled,
+                    &mut self.control,
+                    len + 1,
+                )
+            }),
+            Entry::Occupied(o) => Entry::Occupied(unsafe {
+                OccupEntry::heap(
+                    heap_ptr,
+                    o,
+                    &mut self.stack_keys,
+                    &mut self.stack_values,
+                    &mut self.stack_lookup,
+                    &mut self.recycled,
+                    &mut self.control,
+                )
+            }),
+        }
+    }
+}
+
+impl<K: Ord, V, const N: usize> SortedRolloverMap<K, V, N> {
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.stack_keys
+            .first()
+            .zip(self.stack_values.first())
+            .or_else(|| self.heap.first_key_value())
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        match self.stack_keys.len() {
+            0 => self.heap.last_key_value(),
+            len => Some((&self.stack_keys[len - 1], &self.stack_values[len - 1])),
+        }
+    }
+}
+
+impl<K: PartialEq, V, C: Comparator<K>, const N: usize, B: HeapBackend, S, P: SpillControl>
+    RolloverHashedOptHeap<K, V, C, N, B, S, P>
+{
     pub fn top_key(&self) -> Option<&K> {
+        self.peek().map(|(key, _)| key)
+    }
+
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        self.heap.peek().or_else(|| {
+            let index = self.stack_lookup.best()?;
+            Some((&self.stack_keys[index], &self.stack_values[index]))
+        })
+    }
+
+    pub fn pop_top(&mut self) -> Option<(K, V)> {
+        if !self.heap.is_empty() {
+            return self.pop_heap_top();
+        }
+        let index = self.stack_lookup.best()?;
+        Some(self.remove_stack_entry(index))
+    }
+
+    pub fn iter_sorted(&self) -> SortedIter<'_, K, V, C, B, S> {
+        if self.heap.is_empty() {
+            SortedIter::stack(
+                self.stack_keys
+                    .iter()
+                    .zip(self.stack_values.iter())
+                    .collect(),
+                self.heap.comparator(),
+            )
+        } else {
+            SortedIter::heap(self.heap.iter_sorted())
+        }
+    }
+
+    pub fn top_k(&self, k: usize) -> Vec<(&K, &V)> {
+        self.iter_sorted().take(k).collect()
+    }
+
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, K, V, C, B, S> {
+        if self.heap.is_empty() {
+            let len = self.stack_keys.len();
+            self.stack_lookup = Ranked::default();
+            DrainSorted::stack(
+                self.stack_keys
+                    .drain(..)
+                    .zip(self.stack_values.drain(..len))
+                    .collect(),
+                self.heap.comparator(),
+            )
+        } else {
+            DrainSorted::heap(self.heap.drain_sorted())
+        }
+    }
+
+    pub fn append(&mut self, other: &mut Self)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        self.merge_with(other, |_, value, other| *value = other);
+    }
+
+    pub fn merge_with<F>(&mut self, other: &mut Self, mut merge: F)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+        F: FnMut(&K, &mut V, V),
+    {
+        if self.heap.is_empty() && self.control.policy(N).fits_stack(self.len() + other.len()) {
+            for (key, value) in other.drain() {
+                match self.stack_lookup.find(&self.stack_keys, &key) {
+                    Some(i) => merge(&self.stack_keys[i], &mut self.stack_values[i], value),
+                    None => {
+                        push_stack(
+                            &mut self.stack_keys,
+                            &mut self.stack_values,
+                            &mut self.stack_lookup,
+                            &self.heap,
+                            key,
+                            value,
+                        );
+                    }
+                }
+            }
+            self.control.grew(self.stack_keys.len());
+            return;
+        }
+        if self.heap.is_empty() {
+            spill(
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.control,
+                &mut self.heap,
+            );
+        }
+        self.heap.merge_with(&mut other.heap, &mut merge);
+        let len = other.stack_keys.len();
+        other.stack_lookup = Ranked::default();
+        self.heap.extend_with(
+            other
+                .stack_keys
+                .drain(..)
+                .zip(other.stack_values.drain(..len)),
+            merge,
+        );
+        self.control.grew(self.heap.len());
+        unspill(
+            self.heap.len(),
+            &mut self.heap,
+            |heap| heap.drain().collect(),
+            &mut self.stack_keys,
+            &mut self.stack_values,
+            &mut self.stack_lookup,
+            &mut self.control,
+        );
+    }
+
+    pub fn into_sorted_vec(mut self) -> Vec<(K, V)> {
+        self.drain_sorted().collect()
+    }
+}
+
+impl<K, V, C, const N: usize, B: HeapBackend, P: SpillControl>
+    RolloverHashedOptHeap<K, V, C, N, B, RandomState, P>
+{
+    pub fn with_comparator(compare: C) -> Self {
+        Self::with_comparator_and_hasher(compare, RandomState::new())
+    }
+}
+
+impl<K, V, C, const N: usize, B: HeapBackend, S, P: SpillControl>
+    RolloverHashedOptHeap<K, V, C, N, B, S, P>
+{
+    pub fn with_hasher(hasher: S) -> Self
+    where
+        C: Default,
+    {
+        Self::from_heap(HashedHeap::with_hasher(hasher))
+    }
+
+    pub fn with_comparator_and_hasher(compare: C, hasher: S) -> Self {
+        Self::from_heap(HashedHeap::with_comparator_and_hasher(compare, hasher))
+    }
+
+    fn from_heap(heap: HashedHeap<K, V, C, B, S>) -> Self {
+        Self {
+            stack_keys: ArrayVec::new(),
+            stack_values: ArrayVec::new(),
+            stack_lookup: Ranked::default(),
+            heap,
+            recycled: NoRecycle,
+            control: P::fixed(N),
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        self.heap.comparator()
+    }
+
+    fn pop_heap_top(&mut self) -> Option<(K, V)>
+    where
+        K: PartialEq,
+        C: Comparator<K>,
+    {
+        let result = self.heap.pop_top();
+        unspill(
+            self.heap.len(),
+            &mut self.heap,
+            |heap| heap.drain().collect(),
+            &mut self.stack_keys,
+            &mut self.stack_values,
+            &mut self.stack_lookup,
+            &mut self.control,
+        );
+        result
+    }
+}
+
+impl<K, V, C, const N: usize, B: DoubleEndedBackend, S, P: SpillControl>
+    RolloverHashedOptHeap<K, V, C, N, B, S, P>
+{
+    fn pop_heap_bottom(&mut self) -> Option<(K, V)>
+    where
+        K: PartialEq,
+        C: Comparator<K>,
+    {
+        let result = self.heap.pop_bottom();
+        unspill(
+            self.heap.len(),
+            &mut self.heap,
+            |heap| heap.drain().collect(),
+            &mut self.stack_keys,
+            &mut self.stack_values,
+            &mut self.stack_lookup,
+            &mut self.control,
+        );
+        result
+    }
+}
+
+impl<K: PartialOrd, V, const N: usize, S, P: SpillControl> RolloverHashedMaxHeap<K, V, N, S, P> {
+    pub fn max_key(&self) -> Option<&K> {
+        self.top_key()
+    }
+
+    pub fn pop_max(&mut self) -> Option<(K, V)> {
+        self.pop_top()
+    }
+}
+
+impl<K: PartialOrd, V, const N: usize, S, P: SpillControl> RolloverHashedMinHeap<K, V, N, S, P> {
+    pub fn min_key(&self) -> Option<&K> {
+        self.top_key()
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        self.pop_top()
+    }
+}
+
+impl<K: PartialOrd, V, const N: usize, S, P: SpillControl> RolloverHashedMinMaxHeap<K, V, N, S, P> {
+    pub fn min_key(&self) -> Option<&K> {
+        self.top_key()
+    }
+
+    pub fn max_key(&self) -> Option<&K> {
+        self.heap.max_key().or_else(|| {
+            let comparator = self.heap.comparator();
+            self.stack_keys
+                .iter()
+                .reduce(|a, b| if comparator.favors(a, b) { b } else { a })
+        })
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        self.pop_top()
+    }
+
+    pub fn pop_max(&mut self) -> Option<(K, V)> {
+        if !self.heap.is_empty() {
+            return self.pop_heap_bottom();
+        }
+        let comparator = self.heap.comparator();
+        let (index, _) = self.stack_keys.iter().enumerate().reduce(|a, b| {
+            if comparator.favors(a.1, b.1) {
+                b
+            } else {
+                a
+            }
+        })?;
+        Some(self.remove_stack_entry(index))
+    }
+}
+
+impl<K, V, C, const N: usize, B: HeapBackend, P: SpillControl>
+    RolloverStableHashedHeap<K, V, C, N, B, P>
+{
+    pub fn with_comparator(compare: C) -> Self {
+        Self {
+            stack_keys: ArrayVec::new(),
+            stack_values: ArrayVec::new(),
+            stack_lookup: Ordered,
+            heap: StableHashedHeap::with_comparator(compare),
+            recycled: NoRecycle,
+            control: P::fixed(N),
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        self.heap.comparator()
+    }
+
+    pub fn top_key(&self) -> Option<&K>
+    where
+        C: Comparator<K>,
+    {
         self.heap.top_key().or_else(|| {
-            let comparator = C::default();
+            let comparator = self.heap.comparator();
             self.stack_keys
                 .iter()
                 .reduce(|a, b| comparator.favored(a, b))
         })
     }
-}
 
-impl<K: Ord, V, const N: usize> RolloverHashedMaxHeap<K, V, N> {
-    pub fn max_key(&self) -> Option<&K> {
-        self.heap.max_key().or_else(|| self.stack_keys.iter().max())
+    pub fn pop_top(&mut self) -> Option<(K, V)>
+    where
+        K: PartialEq,
+        C: Comparator<K>,
+    {
+        if !self.heap.is_empty() {
+            let result = self.heap.pop_top();
+            unspill(
+                self.heap.len(),
+                &mut self.heap,
+                |heap| heap.drain().collect(),
+                &mut self.stack_keys,
+                &mut self.stack_values,
+                &mut self.stack_lookup,
+                &mut self.control,
+            );
+            return result;
+        }
+        let comparator = self.heap.comparator();
+        let (index, _) = self.stack_keys.iter().enumerate().reduce(|a, b| {
+            if comparator.favors(b.1, a.1) {
+                b
+            } else {
+                a
+            }
+        })?;
+        Some(self.remove_stack_entry(index))
     }
 }
 
-impl<K: Ord, V, const N: usize> RolloverHashedMinHeap<K, V, N> {
-    pub fn min_key(&self) -> Option<&K> {
-        self.heap.min_key().or_else(|| self.stack_keys.iter().min())
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn pop_min_crosses_back_into_the_stack() {
+        let mut heap = RolloverHashedMinHeap::<u32, char, 2>::new();
+        heap.insert(3, 'c');
+        heap.insert(1, 'a');
+        heap.insert(2, 'b');
+        assert_eq!(heap.min_key(), Some(&1));
+        assert_eq!(heap.pop_min(), Some((1, 'a')));
+        assert_eq!(heap.pop_min(), Some((2, 'b')));
+        assert_eq!(heap.pop_min(), Some((3, 'c')));
+        assert_eq!(heap.pop_min(), None);
+        heap.insert(5, 'e');
+        heap.insert(4, 'd');
+        assert_eq!(heap.pop_min(), Some((4, 'd')));
+        assert_eq!(heap.len(), 1);
+    }
+
+    #[test]
+    fn stack_values_need_no_default_and_drop_once() {
+        let token = Rc::new(());
+        let mut map = RolloverMap::<u32, Rc<()>, 2>::new();
+        for key in 0..3 {
+            map.insert(key, Rc::clone(&token));
+        }
+        assert_eq!(Rc::strong_count(&token), 4);
+        assert!(map.remove(&2).is_some());
+        assert!(map.remove(&0).is_some());
+        assert_eq!(Rc::strong_count(&token), 2);
+        map.insert(5, Rc::clone(&token));
+        assert_eq!(map.drain().count(), 2);
+        assert_eq!(Rc::strong_count(&token), 1);
+        map.insert(6, Rc::clone(&token));
+        drop(map);
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+
+    #[test]
+    fn fingerprints_follow_swap_removed_keys() {
+        let mut map = FingerprintedRolloverMap::<String, u32, 4>::new();
+        for (value, key) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            map.insert(key.to_owned(), value as u32);
+        }
+        assert_eq!(map.remove(&"a".to_owned()), Some(0));
+        assert_eq!(map.stack_keys.as_slice(), ["d", "b", "c"]);
+        assert_eq!(map.get(&"d".to_owned()), Some(&3));
+        map.insert("e".to_owned(), 4);
+        assert_eq!(map.remove(&"b".to_owned()), Some(1));
+        for (key, value) in [("c", 2), ("d", 3), ("e", 4)] {
+            assert_eq!(map.get(&key.to_owned()), Some(&value));
+        }
+        assert!(!map.contains_key(&"b".to_owned()));
+    }
+
+    #[test]
+    fn ordered_stack_keeps_insertion_order_on_removal() {
+        let mut map = RolloverMap::<u32, char, 4, HashMap<u32, char>, Ordered>::new();
+        for (key, value) in [(4, 'd'), (1, 'a'), (3, 'c'), (2, 'b')] {
+            map.insert(key, value);
+        }
+        assert_eq!(map.remove(&1), Some('a'));
+        assert_eq!(map.stack_keys.as_slice(), [4, 3, 2]);
+        let values: Vec<_> = map.iter().map(|(_, &v)| v).collect();
+        assert_eq!(values, ['d', 'c', 'b']);
+    }
+
+    #[test]
+    fn stable_heap_ties_on_the_stack_pop_in_insertion_order() {
+        let rank = comparator::ByKey::with_comparator(
+            |&(rank, _): &(u32, char)| rank,
+            comparator::Min::default(),
+        );
+        let mut heap = RolloverStableHashedHeap::<_, (), _, 4>::with_comparator(rank);
+        for key in [(2, 'a'), (1, 'b'), (2, 'c'), (1, 'd')] {
+            heap.insert(key, ());
+        }
+        heap.remove(&(1, 'b'));
+        let order: Vec<_> = iter::from_fn(|| heap.pop_top()).map(|(k, _)| k).collect();
+        assert_eq!(order, [(1, 'd'), (2, 'a'), (2, 'c')]);
+    }
+
+    #[test]
+    fn pop_max_from_the_stack_keeps_remaining_values() {
+        let mut heap = RolloverHashedMaxHeap::<u32, u32, 4>::new();
+        for key in [2, 7, 5] {
+            heap.insert(key, key * 10);
+        }
+        assert_eq!(heap.pop_max(), Some((7, 70)));
+        assert_eq!(heap.get(&2), Some(&20));
+        assert_eq!(heap.get(&5), Some(&50));
+        assert_eq!(heap.max_key(), Some(&5));
+    }
+
+    #[test]
+    fn sorted_views_agree_on_both_tiers() {
+        let mut heap = RolloverHashedMinHeap::<u32, u32, 3>::new();
+        for key in [8, 2, 5] {
+            heap.insert(key, key);
+        }
+        let stack: Vec<_> = heap.iter_sorted().map(|(&k, _)| k).collect();
+        assert_eq!(stack, [2, 5, 8]);
+        heap.insert(1, 1);
+        let spilled: Vec<_> = heap.iter_sorted().map(|(&k, _)| k).collect();
+        assert_eq!(spilled, [1, 2, 5, 8]);
+        assert_eq!(heap.top_k(2), [(&1, &1), (&2, &2)]);
+        let drained: Vec<_> = heap.drain_sorted().map(|(k, _)| k).collect();
+        assert_eq!(drained, [1, 2, 5, 8]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn min_max_heap_collapses_from_either_end() {
+        let mut heap = RolloverHashedMinMaxHeap::<u32, u32, 2>::new();
+        for key in [5, 1, 9] {
+            heap.insert(key, key);
+        }
+        assert_eq!((heap.min_key(), heap.max_key()), (Some(&1), Some(&9)));
+        assert_eq!(heap.pop_max(), Some((9, 9)));
+        assert_eq!(heap.pop_min(), Some((1, 1)));
+        assert_eq!(heap.pop_max(), Some((5, 5)));
+        assert_eq!(heap.pop_min(), None);
+    }
+
+    type TunedMap = RolloverMap<u32, u32, 4, HashMap<u32, u32>, Linear, NoRecycle, SpillPolicy>;
+
+    fn tiers(map: &TunedMap) -> (usize, usize) {
+        (map.stack_keys.len(), map.heap.len())
+    }
+
+    #[test]
+    fn unspill_threshold_adds_hysteresis() {
+        let mut map = TunedMap::with_policy(SpillPolicy::new(4, 1));
+        map.extend((0..4).map(|k| (k, k)));
+        assert_eq!(tiers(&map), (4, 0));
+        map.insert(4, 4);
+        assert_eq!(tiers(&map), (0, 5));
+        for key in 0..3 {
+            map.remove(&key);
+        }
+        assert_eq!(tiers(&map), (0, 2));
+        map.insert(9, 9);
+        assert_eq!(tiers(&map), (0, 3));
+        map.remove(&9);
+        map.remove(&3);
+        assert_eq!(tiers(&map), (1, 0));
+        assert_eq!(map.get(&4), Some(&4));
+    }
+
+    #[test]
+    fn set_policy_applies_immediately() {
+        let mut map = TunedMap::new();
+        map.extend((0..3).map(|k| (k, k)));
+        map.set_policy(SpillPolicy::fixed(2));
+        assert_eq!(tiers(&map), (0, 3));
+        map.set_policy(SpillPolicy::fixed(3));
+        assert_eq!(tiers(&map), (3, 0));
+        assert_eq!(map.policy(), SpillPolicy::new(3, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "maximum stack length 5 exceeds capacity 4")]
+    fn policies_beyond_capacity_are_rejected() {
+        TunedMap::with_policy(SpillPolicy::fixed(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "unspill length must not exceed")]
+    fn unspill_length_is_capped_by_stack_length() {
+        SpillPolicy::new(2, 3);
+    }
+
+    #[test]
+    fn stats_and_hook_follow_spill_round_trips() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut map = ObservedRolloverMap::<u32, u32, 2>::new();
+        map.insert(0, 0);
+        assert!(map.stats().is_none());
+        map.enable_stats();
+        map.set_spill_hook({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event)
+        });
+        for _ in 0..2 {
+            map.extend([(1, 1), (2, 2)]);
+            assert!(map.is_spilled());
+            assert_eq!((map.inline_len(), map.backing_map().len()), (0, 3));
+            map.remove(&2);
+            assert!(!map.is_spilled());
+            assert_eq!(map.inline_len(), 2);
+            map.remove(&1);
+        }
+        let stats = map.stats().unwrap();
+        assert_eq!(
+            (stats.spills(), stats.collapses(), stats.peak_len()),
+            (2, 2, 3)
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                SpillEvent::Spill { len: 2 },
+                SpillEvent::Collapse { len: 2 },
+                SpillEvent::Spill { len: 2 },
+                SpillEvent::Collapse { len: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn enabling_stats_starts_from_the_current_length() {
+        let mut map = ObservedRolloverMap::<u32, u32, 2>::new();
+        map.extend([(0, 0), (1, 1), (2, 2)]);
+        map.enable_stats();
+        assert_eq!(map.stats().map(SpillStats::peak_len), Some(3));
+        assert_eq!(map.stats().map(SpillStats::spills), Some(0));
+        map.disable_stats();
+        map.insert(3, 3);
+        assert!(map.stats().is_none());
+    }
+
+    #[test]
+    fn cleared_values_are_reused_from_both_tiers() {
+        let mut map = RecyclingRolloverMap::<u32, Vec<u32>, 2>::new();
+        for key in 0..3 {
+            map.insert(key, Vec::with_capacity(8));
+        }
+        assert!(map.remove_clearable(&2));
+        assert_eq!(map.recycled_len(), 1);
+        assert!(!map.is_spilled());
+        assert!(map.remove_clearable(&0));
+        assert!(!map.remove_clearable(&0));
+        assert_eq!(map.recycled_len(), 1);
+        let value = map.entry(7).or_insert_cleared();
+        assert!(value.is_empty() && value.capacity() >= 8);
+        assert_eq!(map.recycled_len(), 1);
+        map.clear_recycled();
+        assert!(map.entry(8).or_insert_cleared().capacity() == 0);
+    }
+
+    #[test]
+    fn drain_or_remove_drains_inline_values_in_place() {
+        let mut map = RolloverMap::<u32, Vec<u32>, 2>::new();
+        map.insert(1, vec![1, 2]);
+        match map.drain_or_remove(&1) {
+            Some(DrainOrRemove::Drained(drained)) => {
+                assert_eq!(drained.collect::<Vec<_>>(), [1, 2])
+            }
+            _ => panic!("inline value was not drained in place"),
+        }
+        assert!(map.drain_or_remove(&1).is_none());
+        assert!(map.entry(3).or_insert_cleared().capacity() >= 2);
+    }
+
+    #[test]
+    fn clear_keeps_inline_values_for_reuse() {
+        let mut map = RolloverMap::<u32, Vec<u32>, 2>::new();
+        map.insert(1, Vec::with_capacity(8));
+        map.insert(2, vec![2]);
+        Clear::clear(&mut map);
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0);
+        map.insert(3, vec![3]);
+        assert_eq!(map.get(&3), Some(&vec![3]));
+        assert!(map.entry(4).or_insert_cleared().capacity() >= 1);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn sorted_inline_tier_iterates_in_key_order() {
+        let mut map = SortedRolloverMap::<u32, char, 3>::new();
+        map.insert(5, 'e');
+        map.insert(1, 'a');
+        map.insert(3, 'c');
+        assert_eq!(map.stack_keys.as_slice(), [1, 3, 5]);
+        assert_eq!(map.first_key_value(), Some((&1, &'a')));
+        assert_eq!(map.last_key_value(), Some((&5, &'e')));
+        assert_eq!(map.insert(3, 'C'), Some('c'));
+        map.insert(4, 'd');
+        assert!(map.is_spilled());
+        assert_eq!(map.last_key_value(), Some((&5, &'e')));
+        map.remove(&1);
+        let keys: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(keys, [(3, 'C'), (4, 'd'), (5, 'e')]);
+        assert!(!map.is_spilled());
+        map.remove(&4);
+        map.insert(0, 'z');
+        let keys: Vec<_> = map.drain().map(|(k, _)| k).collect();
+        assert_eq!(keys, [0, 3, 5]);
+    }
+
+    #[derive(Clone)]
+    struct Nearest(u32);
+
+    impl Comparator<u32> for Nearest {
+        fn favors(&self, a: &u32, b: &u32) -> bool {
+            a.abs_diff(self.0) < b.abs_diff(self.0)
+        }
+    }
+
+    #[test]
+    fn cached_best_follows_inline_edits() {
+        let mut heap = RolloverHashedOptHeap::<u32, (), Nearest, 4>::with_comparator(Nearest(10));
+        for key in [3, 13, 8, 20] {
+            heap.insert(key, ());
+        }
+        assert_eq!(heap.top_key(), Some(&8));
+        heap.remove(&8);
+        assert_eq!(heap.top_key(), Some(&13));
+        heap.remove(&20);
+        heap.insert(9, ());
+        assert_eq!(heap.peek(), Some((&9, &())));
+        let popped: Vec<_> = iter::from_fn(|| heap.pop_top()).map(|(k, _)| k).collect();
+        assert_eq!(popped, [9, 13, 3]);
+    }
+
+    #[test]
+    fn pop_top_collapses_after_a_spill() {
+        let mut heap = RolloverHashedMaxHeap::<u32, u32, 2, RandomState, SpillPolicy>::with_policy(
+            SpillPolicy::new(2, 1),
+        );
+        for key in [4, 9, 1, 7] {
+            heap.insert(key, key * 10);
+        }
+        assert!(heap.is_spilled());
+        assert_eq!(heap.pop_top(), Some((9, 90)));
+        assert_eq!(heap.pop_max(), Some((7, 70)));
+        assert!(heap.is_spilled());
+        assert_eq!(heap.pop_max(), Some((4, 40)));
+        assert!(!heap.is_spilled());
+        heap.insert(6, 60);
+        assert_eq!(heap.peek(), Some((&6, &60)));
+        assert_eq!(heap.pop_top(), Some((6, 60)));
+        assert_eq!(heap.pop_top(), Some((1, 10)));
+        assert_eq!(heap.pop_top(), None);
+    }
+
+    fn take_all<M: Lookup<str, Value = u32>>(map: &mut M, keys: &[&str]) -> Vec<Option<u32>> {
+        keys.iter().map(|key| map.remove(key)).collect()
+    }
+
+    #[test]
+    fn borrowed_lookups_reach_both_tiers() {
+        let mut map = RolloverMap::<String, u32, 2>::new();
+        map.insert("a".to_owned(), 1);
+        map.insert("b".to_owned(), 2);
+        *Lookup::get_mut(&mut map, "b").unwrap() += 10;
+        assert_eq!(take_all(&mut map, &["b", "z"]), [Some(12), None]);
+        for (key, value) in [("c", 3), ("d", 4)] {
+            map.insert(key.to_owned(), value);
+        }
+        assert!(Lookup::contains_key(&map, "d"));
+        assert_eq!(take_all(&mut map, &["a", "d"]), [Some(1), Some(4)]);
+        let mut heap = crate::HashedMinHeap::<String, u32>::new();
+        heap.insert("x".to_owned(), 7);
+        assert_eq!(take_all(&mut heap, &["x", "x"]), [Some(7), None]);
+    }
+
+    #[test]
+    fn spill_control_costs_nothing_by_default() {
+        assert_eq!(
+            mem::size_of::<RolloverMap<u32, u32, 4>>(),
+            mem::size_of::<(ArrayVec<u32, 4>, ArrayVec<u32, 4>, HashMap<u32, u32>)>()
+        );
+        assert_eq!(
+            RolloverMap::<u32, u32, 4>::new().policy(),
+            SpillPolicy::fixed(4)
+        );
+    }
+
+    #[test]
+    fn observed_maps_can_also_be_tuned() {
+        let mut map =
+            ObservedRolloverMap::<u32, u32, 4, HashMap<u32, u32>, SpillPolicy>::with_policy(
+                SpillPolicy::new(2, 0),
+            );
+        map.enable_stats();
+        map.extend([(0, 0), (1, 1), (2, 2)]);
+        map.remove(&0);
+        assert!(map.is_spilled());
+        map.set_policy(SpillPolicy::fixed(3));
+        assert_eq!(map.inline_len(), 2);
+        let stats = map.stats().unwrap();
+        assert_eq!((stats.spills(), stats.collapses()), (1, 1));
+    }
+
+    #[test]
+    fn send_only_hooks_keep_the_map_sync() {
+        fn shared<T: Send + Sync>(value: T) -> T {
+            value
+        }
+        let mut map = ObservedRolloverMap::<u32, u32, 1>::new();
+        let spills = std::cell::Cell::new(0);
+        map.set_spill_hook(move |_| spills.set(spills.get() + 1));
+        let mut map = shared(map);
+        map.extend([(0, 0), (1, 1)]);
+        assert!(map.is_spilled());
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash, PartialOrd)]
+    struct Rank(i32);
+
+    #[test]
+    fn partially_ordered_keys_pop_from_both_tiers() {
+        let mut heap = RolloverHashedMinMaxHeap::<Rank, char, 2>::new();
+        heap.insert(Rank(5), 'b');
+        heap.insert(Rank(-1), 'a');
+        assert_eq!(heap.max_key(), Some(&Rank(5)));
+        assert_eq!(heap.pop_max(), Some((Rank(5), 'b')));
+        heap.insert(Rank(8), 'd');
+        heap.insert(Rank(6), 'c');
+        assert_eq!(heap.pop_min(), Some((Rank(-1), 'a')));
+        assert_eq!(heap.pop_max(), Some((Rank(8), 'd')));
+        let mut max = RolloverHashedMaxHeap::<Rank, char, 2>::new();
+        max.insert(Rank(1), 'x');
+        max.insert(Rank(3), 'y');
+        assert_eq!(max.pop_max(), Some((Rank(3), 'y')));
+    }
+
+    #[derive(Debug)]
+    struct Tagged(u32, char);
+
+    impl PartialEq for Tagged {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Tagged {}
+
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            self.0.partial_cmp(&other.0)
+        }
+    }
+
+    impl Hash for Tagged {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    fn replace_tag<M>(map: &mut M, key: u32, tag: char) -> (char, char)
+    where
+        M: GenericMap<K = Tagged, V = u32> + Lookup<Tagged, Key = Tagged, Value = u32>,
+    {
+        let Entry::Occupied(entry) = map.entry(Tagged(key, '?')) else {
+            panic!("key {} is missing", key);
+        };
+        let (old, _) = entry.replace_entry(Tagged(key, tag), key);
+        (old.1, map.get_key_value(&Tagged(key, '?')).unwrap().0 .1)
+    }
+
+    #[test]
+    fn replace_entry_swaps_keys_on_both_tiers() {
+        let mut heap = RolloverHashedMinHeap::<Tagged, u32, 2>::new();
+        heap.insert(Tagged(1, 'a'), 0);
+        assert_eq!(replace_tag(&mut heap, 1, 'b'), ('a', 'b'));
+        heap.insert(Tagged(2, 'c'), 0);
+        heap.insert(Tagged(3, 'd'), 0);
+        assert!(heap.is_spilled());
+        assert_eq!(replace_tag(&mut heap, 3, 'e'), ('d', 'e'));
+        assert_eq!(
+            heap.pop_min().map(|(key, value)| (key.1, value)),
+            Some(('b', 1))
+        );
+        let mut map = RolloverMap::<Tagged, u32, 1>::new();
+        map.insert(Tagged(1, 'a'), 0);
+        map.insert(Tagged(2, 'b'), 0);
+        assert_eq!(replace_tag(&mut map, 2, 'c'), ('c', 'b'));
+        assert_eq!(map.get(&Tagged(2, '?')), Some(&2));
+    }
+
+    thread_local! {
+        static CLONES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct Counted(u32);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.with(|clones| clones.set(clones.get() + 1));
+            Counted(self.0)
+        }
+    }
+
+    fn clones() -> usize {
+        CLONES.with(Cell::get)
+    }
+
+    #[test]
+    fn entry_ref_clones_keys_only_on_insertion() {
+        let mut map = RolloverMap::<Counted, u32, 2>::new();
+        map.insert(Counted(1), 10);
+        let before = clones();
+        *map.entry_ref(&Counted(1)).or_insert(0) += 1;
+        assert!(matches!(map.entry_ref(&Counted(2)), Entry::Vacant(_)));
+        assert_eq!(clones(), before);
+        map.entry_ref(&Counted(2)).or_insert(20);
+        assert_eq!(clones(), before + 1);
+        assert!(!map.is_spilled());
+
+        map.insert(Counted(3), 30);
+        assert!(map.is_spilled());
+        let before = clones();
+        match map.entry_ref(&Counted(3)) {
+            Entry::Occupied(mut occ) => {
+                assert_eq!(occ.key(), &Counted(3));
+                *occ.get_mut() += 1;
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(matches!(map.entry_ref(&Counted(4)), Entry::Vacant(_)));
+        assert_eq!(clones(), before);
+        map.entry_ref(&Counted(4)).or_insert(40);
+        assert_eq!(clones(), before + 1);
+        assert_eq!(map.get(&Counted(1)), Some(&11));
+        assert_eq!(map.get(&Counted(3)), Some(&31));
+        assert_eq!(map.len(), 4);
     }
 }
@@ -0,0 +1,205 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+use arrayvec::ArrayVec;
+
+use crate::clear::Clear;
+use crate::hashed_heap::backend::HeapBackend;
+use crate::hashed_heap::comparator::Comparator;
+use crate::hashed_heap::HashedHeap;
+
+pub trait StackFind<K, Q: ?Sized = K> {
+    fn find(&self, keys: &[K], key: &Q) -> Option<usize>;
+}
+
+pub trait StackLookup<K, M: ?Sized>: StackFind<K> + Clear {
+    const ORDERED: bool;
+
+    fn insert(&mut self, keys: &[K], key: &K, heap: &M) -> usize;
+    fn remove(&mut self, keys: &[K], index: usize, heap: &M);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Linear;
+
+impl<K: Borrow<Q>, Q: ?Sized + PartialEq> StackFind<K, Q> for Linear {
+    fn find(&self, keys: &[K], key: &Q) -> Option<usize> {
+        keys.iter().position(|k| k.borrow() == key)
+    }
+}
+
+impl<K: PartialEq, M: ?Sized> StackLookup<K, M> for Linear {
+    const ORDERED: bool = false;
+
+    fn insert(&mut self, keys: &[K], _key: &K, _heap: &M) -> usize {
+        keys.len()
+    }
+
+    fn remove(&mut self, _keys: &[K], _index: usize, _heap: &M) {}
+}
+
+impl Clear for Linear {
+    fn clear(&mut self) {}
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ordered;
+
+impl<K: Borrow<Q>, Q: ?Sized + PartialEq> StackFind<K, Q> for Ordered {
+    fn find(&self, keys: &[K], key: &Q) -> Option<usize> {
+        keys.iter().position(|k| k.borrow() == key)
+    }
+}
+
+impl<K: PartialEq, M: ?Sized> StackLookup<K, M> for Ordered {
+    const ORDERED: bool = true;
+
+    fn insert(&mut self, keys: &[K], _key: &K, _heap: &M) -> usize {
+        keys.len()
+    }
+
+    fn remove(&mut self, _keys: &[K], _index: usize, _heap: &M) {}
+}
+
+impl Clear for Ordered {
+    fn clear(&mut self) {}
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sorted;
+
+impl<K: Borrow<Q>, Q: ?Sized + Ord> StackFind<K, Q> for Sorted {
+    fn find(&self, keys: &[K], key: &Q) -> Option<usize> {
+        keys.binary_search_by(|k| k.borrow().cmp(key)).ok()
+    }
+}
+
+impl<K: Ord, M: ?Sized> StackLookup<K, M> for Sorted {
+    const ORDERED: bool = true;
+
+    fn insert(&mut self, keys: &[K], key: &K, _heap: &M) -> usize {
+        keys.partition_point(|k| k < key)
+    }
+
+    fn remove(&mut self, _keys: &[K], _index: usize, _heap: &M) {}
+}
+
+impl Clear for Sorted {
+    fn clear(&mut self) {}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Fingerprinted<const N: usize, S = RandomState> {
+    fingerprints: ArrayVec<u8, N>,
+    hasher: S,
+}
+
+impl<const N: usize, S> Fingerprinted<N, S> {
+    fn fingerprint<Q: ?Sized + Hash>(&self, key: &Q) -> u8
+    where
+        S: BuildHasher,
+    {
+        (self.hasher.hash_one(key) >> 56) as u8
+    }
+}
+
+impl<K, Q, const N: usize, S> StackFind<K, Q> for Fingerprinted<N, S>
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Hash + PartialEq,
+    S: BuildHasher,
+{
+    fn find(&self, keys: &[K], key: &Q) -> Option<usize> {
+        let fingerprint = self.fingerprint(key);
+        self.fingerprints
+            .iter()
+            .zip(keys)
+            .position(|(&f, k)| f == fingerprint && k.borrow() == key)
+    }
+}
+
+impl<K, M, const N: usize, S> StackLookup<K, M> for Fingerprinted<N, S>
+where
+    K: Hash + PartialEq,
+    M: ?Sized,
+    S: BuildHasher,
+{
+    const ORDERED: bool = false;
+
+    fn insert(&mut self, keys: &[K], key: &K, _heap: &M) -> usize {
+        self.fingerprints.push(self.fingerprint(key));
+        keys.len()
+    }
+
+    fn remove(&mut self, _keys: &[K], index: usize, _heap: &M) {
+        self.fingerprints.swap_remove(index);
+    }
+}
+
+impl<const N: usize, S> Clear for Fingerprinted<N, S> {
+    fn clear(&mut self) {
+        self.fingerprints.clear();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ranked<L = Linear> {
+    inner: L,
+    best: Option<usize>,
+}
+
+impl<L> Ranked<L> {
+    pub fn best(&self) -> Option<usize> {
+        self.best
+    }
+}
+
+impl<K, Q: ?Sized, L: StackFind<K, Q>> StackFind<K, Q> for Ranked<L> {
+    fn find(&self, keys: &[K], key: &Q) -> Option<usize> {
+        self.inner.find(keys, key)
+    }
+}
+
+impl<K, V, C, B, S, L> StackLookup<K, HashedHeap<K, V, C, B, S>> for Ranked<L>
+where
+    C: Comparator<K>,
+    B: HeapBackend,
+    L: StackLookup<K, HashedHeap<K, V, C, B, S>>,
+{
+    const ORDERED: bool = L::ORDERED;
+
+    fn insert(&mut self, keys: &[K], key: &K, heap: &HashedHeap<K, V, C, B, S>) -> usize {
+        let compare = heap.comparator();
+        let index = self.inner.insert(keys, key, heap);
+        self.best = match self.best {
+            Some(best) if !compare.favors(key, &keys[best]) => {
+                Some(if best >= index { best + 1 } else { best })
+            }
+            _ => Some(index),
+        };
+        index
+    }
+
+    fn remove(&mut self, keys: &[K], index: usize, heap: &HashedHeap<K, V, C, B, S>) {
+        let compare = heap.comparator();
+        self.inner.remove(keys, index, heap);
+        self.best = match self.best {
+            Some(best) if best == index => keys
+                .iter()
+                .enumerate()
+                .reduce(|a, b| if compare.favors(b.1, a.1) { b } else { a })
+                .map(|(i, _)| i),
+            Some(best) if L::ORDERED && best > index => Some(best - 1),
+            Some(best) if !L::ORDERED && best == keys.len() => Some(index),
+            best => best,
+        };
+    }
+}
+
+impl<L: Clear> Clear for Ranked<L> {
+    fn clear(&mut self) {
+        self.inner.clear();
+        self.best = None;
+    }
+}
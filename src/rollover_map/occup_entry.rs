@@ -4,35 +4,53 @@ use arrayvec::ArrayVec;
 
 use crate::{clear::Clear, GenericMap, OccupiedEntry};
 
-pub struct OccupEntry<'a, K, V, const N: usize, M, E>(OccupEntryInner<'a, K, V, N, M, E>);
+use super::lookup::{Linear, StackLookup};
+use super::recycle::{NoRecycle, Recycle};
+use super::{retire_stack, take_stack, unspill, FixedSpill, SpillControl};
 
-impl<'a, K, V, const N: usize, M, E> OccupEntry<'a, K, V, N, M, E> {
+pub struct OccupEntry<'a, K, V, const N: usize, M, E, L = Linear, R = NoRecycle, P = FixedSpill>(
+    OccupEntryInner<'a, K, V, N, M, E, L, R, P>,
+);
+
+impl<'a, K, V, const N: usize, M, E, L, R, P> OccupEntry<'a, K, V, N, M, E, L, R, P> {
+    #[allow(clippy::too_many_arguments)]
     pub(super) unsafe fn stack(
         index: usize,
         key: *const K,
         value: *mut V,
         stack_keys: &'a mut ArrayVec<K, N>,
-        stack_values: &'a mut [V],
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        heap: &'a M,
     ) -> Self {
         Self(OccupEntryInner::Stack {
             index,
             stack_keys,
             stack_values,
+            stack_lookup,
+            heap,
             key,
             value,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(super) unsafe fn heap(
         heap_ptr: *mut M,
         entry: E,
         stack_keys: &'a mut ArrayVec<K, N>,
-        stack_values: &'a mut [V],
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        recycled: &'a mut R,
+        control: &'a mut P,
     ) -> Self {
         Self(OccupEntryInner::Heap {
             entry,
             stack_keys,
             stack_values,
+            stack_lookup,
+            recycled,
+            control,
             heap_ptr,
         })
     }
@@ -67,11 +85,29 @@ impl<'a, K, V, const N: usize, M, E> OccupEntry<'a, K, V, N, M, E> {
 
     pub fn remove(self) -> V
     where
-        V: Default,
+        L: StackLookup<K, M>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V>,
+        E: OccupiedEntry<'a, K, V>,
+    {
+        self.0.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V)
+    where
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
         E: OccupiedEntry<'a, K, V>,
     {
-        self.0.remove()
+        self.0.remove_entry()
+    }
+
+    pub fn replace_entry(self, key: K, value: V) -> (K, V)
+    where
+        E: OccupiedEntry<'a, K, V>,
+    {
+        self.0.replace_entry(key, value)
     }
 
     pub fn into_mut(self) -> &'a mut V
@@ -84,30 +120,38 @@ impl<'a, K, V, const N: usize, M, E> OccupEntry<'a, K, V, N, M, E> {
     pub fn remove_clearable(self)
     where
         V: Clear,
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
         E: OccupiedEntry<'a, K, V>,
+        R: Recycle<V>,
     {
         self.0.remove_clearable()
     }
 }
 
-enum OccupEntryInner<'a, K, V, const N: usize, M, E> {
+enum OccupEntryInner<'a, K, V, const N: usize, M, E, L, R, P> {
     Stack {
         index: usize,
         stack_keys: &'a mut ArrayVec<K, N>,
-        stack_values: &'a mut [V],
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        heap: &'a M,
         key: *const K,
         value: *mut V,
     },
     Heap {
         entry: E,
         stack_keys: &'a mut ArrayVec<K, N>,
-        stack_values: &'a mut [V],
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        recycled: &'a mut R,
+        control: &'a mut P,
         heap_ptr: *mut M,
     },
 }
 
-impl<'a, K, V, const N: usize, M, E> OccupEntryInner<'a, K, V, N, M, E> {
+impl<'a, K, V, const N: usize, M, E, L, R, P> OccupEntryInner<'a, K, V, N, M, E, L, R, P> {
     pub fn key(&self) -> &K
     where
         E: OccupiedEntry<'a, K, V>,
@@ -150,9 +194,10 @@ impl<'a, K, V, const N: usize, M, E> OccupEntryInner<'a, K, V, N, M, E> {
         }
     }
 
-    pub fn remove(self) -> V
+    pub fn remove_entry(self) -> (K, V)
     where
-        V: Default,
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
         E: OccupiedEntry<'a, K, V>,
     {
@@ -161,32 +206,53 @@ impl<'a, K, V, const N: usize, M, E> OccupEntryInner<'a, K, V, N, M, E> {
                 index,
                 stack_keys,
                 stack_values,
+                stack_lookup,
+                heap,
                 ..
-            } => {
-                stack_keys.remove(index);
-                let result = mem::take(&mut stack_values[index]);
-                stack_values[index..].rotate_left(1);
-                result
-            }
+            } => take_stack(stack_keys, stack_values, stack_lookup, heap, index),
             OccupEntryInner::Heap {
                 entry,
                 stack_keys,
                 stack_values,
+                stack_lookup,
+                control,
                 heap_ptr,
+                ..
             } => {
-                let result = entry.remove();
+                let result = entry.remove_entry();
                 let heap = unsafe { &mut *heap_ptr };
-                if heap.len() == N {
-                    for ((k, v), val) in heap.drain().zip(stack_values.iter_mut()) {
-                        stack_keys.push(k);
-                        *val = v;
-                    }
-                }
+                unspill(
+                    heap.len(),
+                    heap,
+                    |heap| heap.drain().collect(),
+                    stack_keys,
+                    stack_values,
+                    stack_lookup,
+                    control,
+                );
                 result
             }
         }
     }
 
+    pub fn replace_entry(self, key: K, value: V) -> (K, V)
+    where
+        E: OccupiedEntry<'a, K, V>,
+    {
+        match self {
+            OccupEntryInner::Stack {
+                index,
+                stack_keys,
+                stack_values,
+                ..
+            } => (
+                mem::replace(&mut stack_keys[index], key),
+                mem::replace(&mut stack_values[index], value),
+            ),
+            OccupEntryInner::Heap { entry, .. } => entry.replace_entry(key, value),
+        }
+    }
+
     pub fn into_mut(self) -> &'a mut V
     where
         E: OccupiedEntry<'a, K, V>,
@@ -200,44 +266,56 @@ impl<'a, K, V, const N: usize, M, E> OccupEntryInner<'a, K, V, N, M, E> {
     pub fn remove_clearable(self)
     where
         V: Clear,
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
         E: OccupiedEntry<'a, K, V>,
+        R: Recycle<V>,
     {
         match self {
             OccupEntryInner::Stack {
                 index,
                 stack_keys,
                 stack_values,
+                stack_lookup,
+                heap,
                 ..
-            } => {
-                stack_keys.remove(index);
-                stack_values[index].clear();
-                stack_values[index..].rotate_left(1);
-            }
+            } => retire_stack(stack_keys, stack_values, stack_lookup, heap, index).clear(),
             OccupEntryInner::Heap {
                 entry,
                 stack_keys,
                 stack_values,
+                stack_lookup,
+                recycled,
+                control,
                 heap_ptr,
             } => {
-                entry.remove_clearable();
-                let heap = unsafe { &mut *heap_ptr };
-                if heap.len() == N {
-                    for ((k, v), val) in heap.drain().zip(stack_values.iter_mut()) {
-                        stack_keys.push(k);
-                        *val = v;
-                    }
+                if let Ok(value) = recycled.keep(entry.remove()) {
+                    value.clear();
                 }
+                let heap = unsafe { &mut *heap_ptr };
+                unspill(
+                    heap.len(),
+                    heap,
+                    |heap| heap.drain().collect(),
+                    stack_keys,
+                    stack_values,
+                    stack_lookup,
+                    control,
+                );
             }
         }
     }
 }
 
-impl<'a, K, V, const N: usize, M, E> OccupiedEntry<'a, K, V> for OccupEntry<'a, K, V, N, M, E>
+impl<'a, K, V, const N: usize, M, E, L, R, P> OccupiedEntry<'a, K, V>
+    for OccupEntry<'a, K, V, N, M, E, L, R, P>
 where
-    V: Default,
+    L: StackLookup<K, M>,
+    P: SpillControl,
     M: GenericMap<K = K, V = V>,
     E: OccupiedEntry<'a, K, V>,
+    R: Recycle<V>,
 {
     fn key(&self) -> &K {
         self.key()
@@ -259,6 +337,14 @@ where
         self.remove()
     }
 
+    fn remove_entry(self) -> (K, V) {
+        self.remove_entry()
+    }
+
+    fn replace_entry(self, key: K, value: V) -> (K, V) {
+        self.replace_entry(key, value)
+    }
+
     fn into_mut(self) -> &'a mut V {
         self.into_mut()
     }
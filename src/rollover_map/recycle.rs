@@ -0,0 +1,56 @@
+use arrayvec::ArrayVec;
+
+pub trait Recycle<V> {
+    fn take(&mut self) -> Option<V>;
+    fn keep(&mut self, value: V) -> Result<&mut V, V>;
+    fn len(&self) -> usize;
+    fn clear(&mut self);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoRecycle;
+
+impl<V> Recycle<V> for NoRecycle {
+    fn take(&mut self) -> Option<V> {
+        None
+    }
+
+    fn keep(&mut self, value: V) -> Result<&mut V, V> {
+        Err(value)
+    }
+
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn clear(&mut self) {}
+}
+
+impl<V, const P: usize> Recycle<V> for ArrayVec<V, P> {
+    fn take(&mut self) -> Option<V> {
+        self.pop()
+    }
+
+    fn keep(&mut self, value: V) -> Result<&mut V, V> {
+        if P == 0 {
+            return Err(value);
+        }
+        if self.is_full() {
+            self.pop();
+        }
+        self.push(value);
+        Ok(self.last_mut().unwrap())
+    }
+
+    fn len(&self) -> usize {
+        ArrayVec::len(self)
+    }
+
+    fn clear(&mut self) {
+        ArrayVec::clear(self)
+    }
+}
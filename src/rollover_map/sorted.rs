@@ -0,0 +1,84 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::vec;
+
+use crate::hashed_heap::backend::{Binary, HeapBackend};
+use crate::hashed_heap::{self, comparator::Comparator};
+
+pub struct SortedIter<'a, K, V, C, B: HeapBackend = Binary, S = RandomState>(
+    SortedIterInner<'a, K, V, C, B, S>,
+);
+
+enum SortedIterInner<'a, K, V, C, B: HeapBackend, S> {
+    Stack(vec::IntoIter<(&'a K, &'a V)>),
+    Heap(hashed_heap::SortedIter<'a, K, V, C, B, S>),
+}
+
+impl<'a, K, V, C, B: HeapBackend, S> SortedIter<'a, K, V, C, B, S> {
+    pub(super) fn stack(mut entries: Vec<(&'a K, &'a V)>, compare: &C) -> Self
+    where
+        C: Comparator<K>,
+    {
+        entries.sort_by(|(a, _), (b, _)| ordering(compare, a, b));
+        Self(SortedIterInner::Stack(entries.into_iter()))
+    }
+
+    pub(super) fn heap(iter: hashed_heap::SortedIter<'a, K, V, C, B, S>) -> Self {
+        Self(SortedIterInner::Heap(iter))
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>, B: HeapBackend, S> Iterator for SortedIter<'a, K, V, C, B, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            SortedIterInner::Stack(iter) => iter.next(),
+            SortedIterInner::Heap(iter) => iter.next(),
+        }
+    }
+}
+
+pub struct DrainSorted<'a, K, V, C, B: HeapBackend = Binary, S = RandomState>(
+    DrainSortedInner<'a, K, V, C, B, S>,
+);
+
+enum DrainSortedInner<'a, K, V, C, B: HeapBackend, S> {
+    Stack(vec::IntoIter<(K, V)>),
+    Heap(hashed_heap::DrainSorted<'a, K, V, C, B, S>),
+}
+
+impl<'a, K, V, C, B: HeapBackend, S> DrainSorted<'a, K, V, C, B, S> {
+    pub(super) fn stack(mut entries: Vec<(K, V)>, compare: &C) -> Self
+    where
+        C: Comparator<K>,
+    {
+        entries.sort_by(|(a, _), (b, _)| ordering(compare, a, b));
+        Self(DrainSortedInner::Stack(entries.into_iter()))
+    }
+
+    pub(super) fn heap(iter: hashed_heap::DrainSorted<'a, K, V, C, B, S>) -> Self {
+        Self(DrainSortedInner::Heap(iter))
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>, B: HeapBackend, S> Iterator for DrainSorted<'a, K, V, C, B, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            DrainSortedInner::Stack(iter) => iter.next(),
+            DrainSortedInner::Heap(iter) => iter.next(),
+        }
+    }
+}
+
+fn ordering<K, C: Comparator<K>>(compare: &C, a: &K, b: &K) -> Ordering {
+    if compare.favors(a, b) {
+        Ordering::Less
+    } else if compare.favors(b, a) {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
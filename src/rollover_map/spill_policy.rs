@@ -0,0 +1,80 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpillPolicy {
+    max_stack_len: usize,
+    unspill_len: usize,
+}
+
+impl SpillPolicy {
+    pub const fn new(max_stack_len: usize, unspill_len: usize) -> Self {
+        assert!(
+            unspill_len <= max_stack_len,
+            "unspill length must not exceed the maximum stack length"
+        );
+        Self {
+            max_stack_len,
+            unspill_len,
+        }
+    }
+
+    pub const fn fixed(max_stack_len: usize) -> Self {
+        Self::new(max_stack_len, max_stack_len)
+    }
+
+    pub const fn max_stack_len(&self) -> usize {
+        self.max_stack_len
+    }
+
+    pub const fn unspill_len(&self) -> usize {
+        self.unspill_len
+    }
+
+    pub(crate) fn fits_stack(&self, len: usize) -> bool {
+        len <= self.max_stack_len
+    }
+
+    pub(crate) fn should_unspill(&self, heap_len: usize) -> bool {
+        heap_len <= self.unspill_len
+    }
+}
+
+pub trait SpillControl {
+    fn fixed(capacity: usize) -> Self;
+    fn policy(&self, capacity: usize) -> SpillPolicy;
+
+    fn grew(&mut self, _len: usize) {}
+    fn spilled(&mut self, _len: usize) {}
+    fn collapsed(&mut self, _len: usize) {}
+}
+
+pub trait TunableSpill: SpillControl {
+    fn set_policy(&mut self, policy: SpillPolicy);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedSpill;
+
+impl SpillControl for FixedSpill {
+    fn fixed(_capacity: usize) -> Self {
+        FixedSpill
+    }
+
+    fn policy(&self, capacity: usize) -> SpillPolicy {
+        SpillPolicy::fixed(capacity)
+    }
+}
+
+impl SpillControl for SpillPolicy {
+    fn fixed(capacity: usize) -> Self {
+        SpillPolicy::fixed(capacity)
+    }
+
+    fn policy(&self, _capacity: usize) -> SpillPolicy {
+        *self
+    }
+}
+
+impl TunableSpill for SpillPolicy {
+    fn set_policy(&mut self, policy: SpillPolicy) {
+        *self = policy;
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+use super::spill_policy::{FixedSpill, SpillControl, SpillPolicy, TunableSpill};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpillStats {
+    spills: u64,
+    collapses: u64,
+    peak_len: usize,
+}
+
+impl SpillStats {
+    pub const fn spills(&self) -> u64 {
+        self.spills
+    }
+
+    pub const fn collapses(&self) -> u64 {
+        self.collapses
+    }
+
+    pub const fn peak_len(&self) -> usize {
+        self.peak_len
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpillEvent {
+    Spill { len: usize },
+    Collapse { len: usize },
+}
+
+pub type SpillHook = Box<dyn FnMut(SpillEvent) + Send>;
+
+struct SyncHook(SpillHook);
+
+unsafe impl Sync for SyncHook {}
+
+pub struct Observed<P = FixedSpill> {
+    control: P,
+    stats: Option<SpillStats>,
+    hook: Option<SyncHook>,
+}
+
+impl<P> Observed<P> {
+    pub(super) fn stats(&self) -> Option<&SpillStats> {
+        self.stats.as_ref()
+    }
+
+    pub(super) fn enable_stats(&mut self, len: usize) {
+        self.stats = Some(SpillStats {
+            peak_len: len,
+            ..SpillStats::default()
+        });
+    }
+
+    pub(super) fn disable_stats(&mut self) {
+        self.stats = None;
+    }
+
+    pub(super) fn set_hook(&mut self, hook: Option<SpillHook>) {
+        self.hook = hook.map(SyncHook);
+    }
+
+    fn call_hook(&mut self, event: SpillEvent) {
+        if let Some(SyncHook(hook)) = &mut self.hook {
+            hook(event);
+        }
+    }
+}
+
+impl<P: SpillControl> SpillControl for Observed<P> {
+    fn fixed(capacity: usize) -> Self {
+        Self {
+            control: P::fixed(capacity),
+            stats: None,
+            hook: None,
+        }
+    }
+
+    fn policy(&self, capacity: usize) -> SpillPolicy {
+        self.control.policy(capacity)
+    }
+
+    fn grew(&mut self, len: usize) {
+        if let Some(stats) = &mut self.stats {
+            stats.peak_len = stats.peak_len.max(len);
+        }
+    }
+
+    fn spilled(&mut self, len: usize) {
+        if let Some(stats) = &mut self.stats {
+            stats.spills += 1;
+            stats.peak_len = stats.peak_len.max(len);
+        }
+        self.call_hook(SpillEvent::Spill { len });
+    }
+
+    fn collapsed(&mut self, len: usize) {
+        if let Some(stats) = &mut self.stats {
+            stats.collapses += 1;
+        }
+        self.call_hook(SpillEvent::Collapse { len });
+    }
+}
+
+impl<P: TunableSpill> TunableSpill for Observed<P> {
+    fn set_policy(&mut self, policy: SpillPolicy) {
+        self.control.set_policy(policy);
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for Observed<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observed")
+            .field("control", &self.control)
+            .field("stats", &self.stats)
+            .field("hook", &self.hook.is_some())
+            .finish()
+    }
+}
@@ -1,72 +1,184 @@
-use std::mem;
-
 use arrayvec::ArrayVec;
 
-use crate::{Entry, GenericMap, VacantEntry};
+use crate::entry_ref::KeySource;
+use crate::{Entry, GenericMap, OccupiedEntry, VacantEntry};
+
+use super::lookup::{Linear, StackLookup};
+use super::recycle::{NoRecycle, Recycle};
+use super::{push_stack, spill, FixedSpill, OccupEntry, SpillControl};
 
-pub struct VacEntry<'a, K, V, const N: usize, M, E>(VacEntryInner<'a, K, V, N, M, E>);
+pub struct VacEntry<
+    'a,
+    K,
+    V,
+    const N: usize,
+    M,
+    E,
+    L = Linear,
+    R = NoRecycle,
+    P = FixedSpill,
+    T = K,
+>(VacEntryInner<'a, K, V, N, M, E, L, R, P, T>);
 
-impl<'a, K, V, const N: usize, M, E> VacEntry<'a, K, V, N, M, E> {
+impl<'a, K, V, const N: usize, M, E, L, R, P, T> VacEntry<'a, K, V, N, M, E, L, R, P, T> {
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn stack(
-        key: K,
+        key: T,
         stack_keys: &'a mut ArrayVec<K, N>,
-        stack_values: &'a mut [V; N],
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        recycled: &'a mut R,
+        control: &'a mut P,
         heap: &'a mut M,
     ) -> Self {
         Self(VacEntryInner::Stack {
             key,
             stack_keys,
             stack_values,
+            stack_lookup,
+            recycled,
+            control,
             heap,
         })
     }
 
-    pub(super) fn heap(entry: E) -> Self {
-        Self(VacEntryInner::Heap(entry))
+    #[allow(clippy::too_many_arguments)]
+    pub(super) unsafe fn heap(
+        heap_ptr: *mut M,
+        entry: E,
+        stack_keys: &'a mut ArrayVec<K, N>,
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        recycled: &'a mut R,
+        control: &'a mut P,
+        len: usize,
+    ) -> Self {
+        Self(VacEntryInner::Heap {
+            entry,
+            stack_keys,
+            stack_values,
+            stack_lookup,
+            recycled,
+            control,
+            heap_ptr,
+            len,
+        })
     }
 
     pub fn key(&self) -> &K
     where
+        T: KeySource<K>,
         E: VacantEntry<'a, K, V>,
     {
         self.0.key()
     }
 
     pub fn insert(self, value: V) -> &'a mut V
+    where
+        L: StackLookup<K, M>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V>,
+        T: KeySource<K>,
+        E: VacantEntry<'a, K, V>,
+    {
+        self.0.insert(value)
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V, N, M, M::OccupEntry<'a>, L, R, P>
+    where
+        L: StackLookup<K, M>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V>,
+        T: KeySource<K>,
+        E: VacantEntry<'a, K, V, Occupied = M::OccupEntry<'a>>,
+    {
+        self.0.insert_entry(value)
+    }
+
+    pub fn insert_cleared(mut self) -> &'a mut V
     where
         V: Default,
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
+        T: KeySource<K>,
         E: VacantEntry<'a, K, V>,
+        R: Recycle<V>,
     {
+        let value = match self.0.retired() {
+            Some(value) => value,
+            None => self.0.recycled().take().unwrap_or_default(),
+        };
         self.0.insert(value)
     }
 }
 
-enum VacEntryInner<'a, K, V, const N: usize, M, E> {
+enum VacEntryInner<'a, K, V, const N: usize, M, E, L, R, P, T> {
     Stack {
-        key: K,
+        key: T,
         stack_keys: &'a mut ArrayVec<K, N>,
-        stack_values: &'a mut [V],
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        recycled: &'a mut R,
+        control: &'a mut P,
         heap: &'a mut M,
     },
-    Heap(E),
+    Heap {
+        entry: E,
+        stack_keys: &'a mut ArrayVec<K, N>,
+        stack_values: &'a mut ArrayVec<V, N>,
+        stack_lookup: &'a mut L,
+        recycled: &'a mut R,
+        control: &'a mut P,
+        heap_ptr: *mut M,
+        len: usize,
+    },
 }
 
-impl<'a, K, V, const N: usize, M, E> VacEntryInner<'a, K, V, N, M, E> {
+impl<'a, K, V, const N: usize, M, E, L, R, P, T> VacEntryInner<'a, K, V, N, M, E, L, R, P, T> {
     pub fn key(&self) -> &K
     where
+        T: KeySource<K>,
         E: VacantEntry<'a, K, V>,
     {
         match self {
-            VacEntryInner::Stack { key, .. } => key,
-            VacEntryInner::Heap(entry) => entry.key(),
+            VacEntryInner::Stack { key, .. } => key.key(),
+            VacEntryInner::Heap { entry, .. } => entry.key(),
+        }
+    }
+
+    fn retired(&mut self) -> Option<V> {
+        let (VacEntryInner::Stack {
+            stack_keys,
+            stack_values,
+            ..
+        }
+        | VacEntryInner::Heap {
+            stack_keys,
+            stack_values,
+            ..
+        }) = self;
+        if stack_values.len() > stack_keys.len() {
+            stack_values.pop()
+        } else {
+            None
+        }
+    }
+
+    fn recycled(&mut self) -> &mut R {
+        match self {
+            VacEntryInner::Stack { recycled, .. } | VacEntryInner::Heap { recycled, .. } => {
+                recycled
+            }
         }
     }
 
     pub fn insert(self, value: V) -> &'a mut V
     where
-        V: Default,
+        L: StackLookup<K, M>,
+        P: SpillControl,
         M: GenericMap<K = K, V = V>,
+        T: KeySource<K>,
         E: VacantEntry<'a, K, V>,
     {
         match self {
@@ -74,33 +186,131 @@ impl<'a, K, V, const N: usize, M, E> VacEntryInner<'a, K, V, N, M, E> {
                 key,
                 stack_keys,
                 stack_values,
+                stack_lookup,
+                control,
                 heap,
+                ..
             } => {
-                if stack_keys.len() < N {
-                    stack_keys.push(key);
-                    let v = &mut stack_values[stack_keys.len() - 1];
-                    *v = value;
-                    v
+                let key = key.into_key();
+                let len = stack_keys.len() + 1;
+                control.grew(len);
+                if control.policy(N).fits_stack(len) {
+                    let index =
+                        push_stack(stack_keys, stack_values, stack_lookup, heap, key, value);
+                    &mut stack_values[index]
                 } else {
-                    heap.extend(
-                        stack_keys
-                            .drain(..)
-                            .zip(stack_values.iter_mut().map(mem::take)),
-                    );
+                    spill(stack_keys, stack_values, stack_lookup, control, heap);
                     match heap.entry(key) {
                         Entry::Vacant(vac) => vac.insert(value),
-                        Entry::Occupied(_) => panic!("Bad map implementation"),
+                        Entry::Occupied(mut occ) => {
+                            occ.insert(value);
+                            occ.into_mut()
+                        }
                     }
                 }
             }
-            VacEntryInner::Heap(entry) => entry.insert(value),
+            VacEntryInner::Heap {
+                entry,
+                control,
+                len,
+                ..
+            } => {
+                control.grew(len);
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn insert_entry(self, value: V) -> OccupEntry<'a, K, V, N, M, M::OccupEntry<'a>, L, R, P>
+    where
+        L: StackLookup<K, M>,
+        P: SpillControl,
+        M: GenericMap<K = K, V = V>,
+        T: KeySource<K>,
+        E: VacantEntry<'a, K, V, Occupied = M::OccupEntry<'a>>,
+    {
+        match self {
+            VacEntryInner::Stack {
+                key,
+                stack_keys,
+                stack_values,
+                stack_lookup,
+                recycled,
+                control,
+                heap,
+            } => {
+                let key = key.into_key();
+                let len = stack_keys.len() + 1;
+                control.grew(len);
+                if control.policy(N).fits_stack(len) {
+                    let index =
+                        push_stack(stack_keys, stack_values, stack_lookup, heap, key, value);
+                    let k = &stack_keys[index] as *const K;
+                    let v = &mut stack_values[index] as *mut V;
+                    return unsafe {
+                        OccupEntry::stack(index, k, v, stack_keys, stack_values, stack_lookup, heap)
+                    };
+                }
+                spill(stack_keys, stack_values, stack_lookup, control, heap);
+                let heap_ptr = heap as *mut M;
+                let entry = match unsafe { &mut *heap_ptr }.entry(key) {
+                    Entry::Vacant(vac) => vac.insert_entry(value),
+                    Entry::Occupied(mut occ) => {
+                        occ.insert(value);
+                        occ
+                    }
+                };
+                unsafe {
+                    OccupEntry::heap(
+                        heap_ptr,
+                        entry,
+                        stack_keys,
+                        stack_values,
+                        stack_lookup,
+                        recycled,
+                        control,
+                    )
+                }
+            }
+            VacEntryInner::Heap {
+                entry,
+                stack_keys,
+                stack_values,
+                stack_lookup,
+                recycled,
+                control,
+                heap_ptr,
+                len,
+            } => {
+                control.grew(len);
+                unsafe {
+                    OccupEntry::heap(
+                        heap_ptr,
+                        entry.insert_entry(value),
+                        stack_keys,
+                        stack_values,
+                        stack_lookup,
+                        recycled,
+                        control,
+                    )
+                }
+            }
         }
     }
 }
 
-impl<'a, K, V: Default, const N: usize, M: GenericMap<K = K, V = V>, E: VacantEntry<'a, K, V>>
-    VacantEntry<'a, K, V> for VacEntry<'a, K, V, N, M, E>
+impl<'a, K, V, const N: usize, M, E, L, R, P, T> VacantEntry<'a, K, V>
+    for VacEntry<'a, K, V, N, M, E, L, R, P, T>
+where
+    M: GenericMap<K = K, V = V>,
+    T: KeySource<K>,
+    E: VacantEntry<'a, K, V, Occupied = M::OccupEntry<'a>>,
+    L: StackLookup<K, M>,
+    P: SpillControl,
+    R: Recycle<V>,
 {
+    type Occupied = OccupEntry<'a, K, V, N, M, M::OccupEntry<'a>, L, R, P>;
+
     fn key(&self) -> &K {
         self.key()
     }
@@ -108,4 +318,8 @@ impl<'a, K, V: Default, const N: usize, M: GenericMap<K = K, V = V>, E: VacantEn
     fn insert(self, value: V) -> &'a mut V {
         self.insert(value)
     }
+
+    fn insert_entry(self, value: V) -> Self::Occupied {
+        self.insert_entry(value)
+    }
 }
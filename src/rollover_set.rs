@@ -0,0 +1,472 @@
+use std::collections::{BTreeSet, HashSet};
+use std::{iter, slice};
+
+use arrayvec::ArrayVec;
+
+use crate::rollover_map::lookup::{Fingerprinted, Linear, Sorted, StackFind, StackLookup};
+use crate::rollover_map::SpillPolicy;
+use crate::{clear::Clear, drain::Drain, GenericSet, SetLookup};
+
+#[derive(Debug)]
+pub struct RolloverSet<K, const N: usize = 1, S = HashSet<K>, L = Linear> {
+    stack: ArrayVec<K, N>,
+    stack_lookup: L,
+    heap: S,
+    policy: SpillPolicy,
+}
+
+pub type FingerprintedRolloverSet<K, const N: usize = 1, S = HashSet<K>> =
+    RolloverSet<K, N, S, Fingerprinted<N>>;
+
+pub type SortedRolloverSet<K, const N: usize = 1> = RolloverSet<K, N, BTreeSet<K>, Sorted>;
+
+pub type Iter<'a, K, I> = iter::Chain<slice::Iter<'a, K>, I>;
+pub type DrainIter<'a, K, const N: usize, I> = iter::Chain<arrayvec::Drain<'a, K, N>, I>;
+
+impl<K, const N: usize, S: Default, L: Default> Default for RolloverSet<K, N, S, L> {
+    fn default() -> Self {
+        Self {
+            stack: ArrayVec::new(),
+            stack_lookup: L::default(),
+            heap: Default::default(),
+            policy: SpillPolicy::fixed(N),
+        }
+    }
+}
+
+impl<K, const N: usize, S: Clear, L: Clear> Clear for RolloverSet<K, N, S, L> {
+    fn clear(&mut self) {
+        self.stack.clear();
+        self.stack_lookup.clear();
+        self.heap.clear();
+    }
+}
+
+impl<K, const N: usize, S: GenericSet<T = K>, L: Clear> Drain for RolloverSet<K, N, S, L> {
+    type Output<'a> = DrainIter<'a, K, N, S::DrainIter<'a>>
+    where
+        Self: 'a;
+
+    fn drain(&mut self) -> Self::Output<'_> {
+        RolloverSet::drain(self)
+    }
+}
+
+impl<K, const N: usize, S: IntoIterator<Item = K>, L> IntoIterator for RolloverSet<K, N, S, L> {
+    type Item = K;
+    type IntoIter = iter::Chain<arrayvec::IntoIter<K, N>, S::IntoIter>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.stack.into_iter().chain(self.heap)
+    }
+}
+
+impl<'a, K, const N: usize, S: GenericSet<T = K>, L> IntoIterator for &'a RolloverSet<K, N, S, L> {
+    type Item = &'a K;
+    type IntoIter = Iter<'a, K, S::Iter<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, const N: usize, S: GenericSet<T = K>, L: StackLookup<K, S>> Extend<K>
+    for RolloverSet<K, N, S, L>
+{
+    fn extend<T: IntoIterator<Item = K>>(&mut self, iter: T) {
+        let mut iter = iter.into_iter();
+        while self.heap.is_empty() {
+            let Some(key) = iter.next() else {
+                return;
+            };
+            self.insert(key);
+        }
+        self.heap.extend(iter);
+    }
+}
+
+impl<K, const N: usize, S, L> FromIterator<K> for RolloverSet<K, N, S, L>
+where
+    S: GenericSet<T = K> + Default,
+    L: StackLookup<K, S> + Default,
+{
+    fn from_iter<T: IntoIterator<Item = K>>(iter: T) -> Self {
+        let mut set = Self::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<K, const N: usize, S: GenericSet<T = K>, L: StackFind<K>> PartialEq
+    for RolloverSet<K, N, S, L>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.is_subset(other)
+    }
+}
+
+impl<K, const N: usize, S: GenericSet<T = K>, L: StackFind<K>> Eq for RolloverSet<K, N, S, L> {}
+
+fn spill<K, const N: usize, S: GenericSet<T = K>, L: StackLookup<K, S>>(
+    stack: &mut ArrayVec<K, N>,
+    stack_lookup: &mut L,
+    heap: &mut S,
+) {
+    stack_lookup.clear();
+    heap.extend(stack.drain(..));
+}
+
+fn unspill<K, const N: usize, S: GenericSet<T = K>, L: StackLookup<K, S>>(
+    policy: SpillPolicy,
+    stack: &mut ArrayVec<K, N>,
+    stack_lookup: &mut L,
+    heap: &mut S,
+) {
+    if policy.should_unspill(heap.len()) {
+        let keys: ArrayVec<K, N> = heap.drain().collect();
+        for key in keys {
+            push_stack(stack, stack_lookup, heap, key);
+        }
+    }
+}
+
+fn push_stack<K, const N: usize, S, L: StackLookup<K, S>>(
+    stack: &mut ArrayVec<K, N>,
+    stack_lookup: &mut L,
+    heap: &S,
+    key: K,
+) {
+    let index = stack_lookup.insert(stack, &key, heap);
+    stack.insert(index, key);
+}
+
+impl<K, const N: usize, S, L> RolloverSet<K, N, S, L> {
+    pub fn new() -> Self
+    where
+        S: Default,
+        L: Default,
+    {
+        Self::default()
+    }
+
+    pub fn with_policy(policy: SpillPolicy) -> Self
+    where
+        S: Default,
+        L: Default,
+    {
+        Self {
+            policy: Self::checked(policy),
+            ..Self::default()
+        }
+    }
+
+    pub fn policy(&self) -> SpillPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SpillPolicy)
+    where
+        S: GenericSet<T = K>,
+        L: StackLookup<K, S>,
+    {
+        self.policy = Self::checked(policy);
+        if self.heap.is_empty() {
+            if !policy.fits_stack(self.stack.len()) {
+                spill(&mut self.stack, &mut self.stack_lookup, &mut self.heap);
+            }
+        } else {
+            unspill(
+                policy,
+                &mut self.stack,
+                &mut self.stack_lookup,
+                &mut self.heap,
+            );
+        }
+    }
+
+    pub fn len(&self) -> usize
+    where
+        S: GenericSet,
+    {
+        self.stack.len() + self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    where
+        S: GenericSet,
+    {
+        self.stack.is_empty() && self.heap.is_empty()
+    }
+
+    pub fn is_spilled(&self) -> bool
+    where
+        S: GenericSet,
+    {
+        !self.heap.is_empty()
+    }
+
+    pub fn inline_len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn backing_set(&self) -> &S {
+        &self.heap
+    }
+
+    pub fn contains<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        L: StackFind<K, Q>,
+        S: SetLookup<Q>,
+    {
+        self.stack_lookup.find(&self.stack, key).is_some() || self.heap.contains(key)
+    }
+
+    pub fn insert(&mut self, key: K) -> bool
+    where
+        S: GenericSet<T = K>,
+        L: StackLookup<K, S>,
+    {
+        if self.stack_lookup.find(&self.stack, &key).is_some() {
+            return false;
+        }
+        if self.heap.is_empty() {
+            if self.policy.fits_stack(self.stack.len() + 1) {
+                push_stack(&mut self.stack, &mut self.stack_lookup, &self.heap, key);
+                return true;
+            }
+            spill(&mut self.stack, &mut self.stack_lookup, &mut self.heap);
+        }
+        self.heap.insert(key)
+    }
+
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> bool
+    where
+        S: GenericSet<T = K> + SetLookup<Q, Value = K>,
+        L: StackLookup<K, S> + StackFind<K, Q>,
+    {
+        self.take(key).is_some()
+    }
+
+    pub fn take<Q: ?Sized>(&mut self, key: &Q) -> Option<K>
+    where
+        S: GenericSet<T = K> + SetLookup<Q, Value = K>,
+        L: StackLookup<K, S> + StackFind<K, Q>,
+    {
+        if let Some(i) = self.stack_lookup.find(&self.stack, key) {
+            let key = if L::ORDERED {
+                self.stack.remove(i)
+            } else {
+                self.stack.swap_remove(i)
+            };
+            self.stack_lookup.remove(&self.stack, i, &self.heap);
+            return Some(key);
+        }
+        let result = self.heap.take(key)?;
+        unspill(
+            self.policy,
+            &mut self.stack,
+            &mut self.stack_lookup,
+            &mut self.heap,
+        );
+        Some(result)
+    }
+
+    pub fn drain(&mut self) -> DrainIter<'_, K, N, S::DrainIter<'_>>
+    where
+        S: GenericSet<T = K>,
+        L: Clear,
+    {
+        self.stack_lookup.clear();
+        self.stack.drain(..).chain(self.heap.drain())
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, S::Iter<'_>>
+    where
+        S: GenericSet<T = K>,
+    {
+        self.stack.iter().chain(self.heap.iter())
+    }
+
+    pub fn difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a K> + 'a
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        self.iter().filter(move |key| !other.contains(key))
+    }
+
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a K> + 'a
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        self.difference(other).chain(other.difference(self))
+    }
+
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a K> + 'a
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        small.iter().filter(move |key| large.contains(key))
+    }
+
+    pub fn union<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a K> + 'a
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        large.iter().chain(small.difference(large))
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        self.intersection(other).next().is_none()
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        self.len() <= other.len() && self.iter().all(|key| other.contains(key))
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool
+    where
+        S: GenericSet<T = K>,
+        L: StackFind<K>,
+    {
+        other.is_subset(self)
+    }
+
+    fn checked(policy: SpillPolicy) -> SpillPolicy {
+        assert!(
+            policy.max_stack_len() <= N,
+            "maximum stack length {} exceeds capacity {}",
+            policy.max_stack_len(),
+            N
+        );
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn sorted<'a>(keys: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut keys: Vec<_> = keys.copied().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn insert_and_remove_report_membership_changes() {
+        let mut set = RolloverSet::<u32, 2>::new();
+        assert!(set.insert(1));
+        assert!(!set.insert(1));
+        assert!(set.insert(2));
+        assert!(set.insert(3));
+        assert!(!set.insert(2));
+        assert!(set.is_spilled());
+        assert!(set.remove(&3));
+        assert!(!set.remove(&3));
+        assert!(!set.is_spilled());
+        assert_eq!(set.take(&1), Some(1));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn unspill_threshold_delays_the_collapse() {
+        let mut set = RolloverSet::<u32, 3, BTreeSet<u32>>::with_policy(SpillPolicy::new(3, 1));
+        set.extend([4, 2, 8, 6]);
+        assert_eq!(
+            set.backing_set().iter().copied().collect::<Vec<_>>(),
+            [2, 4, 6, 8]
+        );
+        set.remove(&8);
+        set.remove(&2);
+        assert!(set.is_spilled());
+        set.remove(&4);
+        assert_eq!((set.inline_len(), set.is_spilled()), (1, false));
+        assert!(set.contains(&6));
+        set.set_policy(SpillPolicy::fixed(0));
+        assert!(set.is_spilled());
+    }
+
+    #[test]
+    fn set_algebra_spans_both_tiers() {
+        let mut inline = RolloverSet::<u32, 4>::new();
+        inline.extend([1, 2, 3]);
+        let mut spilled = RolloverSet::<u32, 4>::new();
+        spilled.extend([2, 3, 4, 5, 6]);
+        assert!(!inline.is_spilled() && spilled.is_spilled());
+        assert_eq!(sorted(inline.intersection(&spilled)), [2, 3]);
+        assert_eq!(sorted(spilled.intersection(&inline)), [2, 3]);
+        assert_eq!(sorted(inline.union(&spilled)), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(sorted(inline.difference(&spilled)), [1]);
+        assert_eq!(sorted(inline.symmetric_difference(&spilled)), [1, 4, 5, 6]);
+        assert!(!inline.is_subset(&spilled));
+        inline.remove(&1);
+        assert!(inline.is_subset(&spilled) && spilled.is_superset(&inline));
+        assert!(!inline.is_disjoint(&spilled));
+    }
+
+    #[test]
+    fn drain_and_clear_empty_both_tiers() {
+        let mut set = RolloverSet::<u32, 2>::new();
+        set.extend([1, 2, 3]);
+        assert_eq!(set.drain().count(), 3);
+        assert!(set.is_empty() && !set.is_spilled());
+        set.extend([4, 5, 6]);
+        Clear::clear(&mut set);
+        assert!(set.is_empty());
+        assert!(set.insert(7));
+        assert_eq!(set.inline_len(), 1);
+    }
+
+    #[test]
+    fn borrowed_lookups_reach_both_tiers() {
+        let mut set = RolloverSet::<String, 2>::new();
+        set.extend(["a".to_string(), "b".to_string()]);
+        assert!(set.contains("a") && !set.contains("c"));
+        set.insert("c".to_string());
+        assert!(set.is_spilled() && set.contains("c"));
+        assert_eq!(set.take("c"), Some("c".to_string()));
+        assert!(set.remove("a"));
+        assert!(!set.remove("a"));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn inline_lookup_strategies_are_pluggable() {
+        let mut fingerprinted = FingerprintedRolloverSet::<u32, 3>::new();
+        fingerprinted.extend([7, 3, 5]);
+        assert!(fingerprinted.remove(&7));
+        assert!(fingerprinted.contains(&5) && !fingerprinted.contains(&7));
+        assert!(fingerprinted.insert(9));
+        assert_eq!(sorted(fingerprinted.iter()), [3, 5, 9]);
+        let mut ordered = SortedRolloverSet::<u32, 3>::new();
+        ordered.extend([7, 3, 5]);
+        assert_eq!(ordered.iter().copied().collect::<Vec<_>>(), [3, 5, 7]);
+        ordered.insert(1);
+        ordered.remove(&7);
+        assert_eq!(ordered.iter().copied().collect::<Vec<_>>(), [1, 3, 5]);
+    }
+}